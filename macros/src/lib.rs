@@ -2,6 +2,8 @@ use proc_macro::{self, TokenStream};
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
 
+mod route;
+
 #[proc_macro_attribute]
 pub fn request_obj(_metadata: TokenStream, input: TokenStream) -> TokenStream {
     let input: proc_macro2::TokenStream = input.into();
@@ -32,10 +34,10 @@ pub fn derive_request(input: TokenStream) -> TokenStream {
 
     let output = quote! {
         impl server::model::Request for #ident {
-            fn string_body_to_obj(body: String) -> Self
+            fn string_body_to_obj(body: String) -> Result<Self, server::model::api_error::ApiError>
                 where Self: ::serde::Serialize + ::serde::Deserialize<'static> + Sized + Clone {
                 let b = &body[..];
-                serde_json::from_str(b).unwrap()
+                serde_json::from_str(b).map_err(server::model::api_error::ApiError::from)
             }
         }
     };
@@ -56,4 +58,66 @@ pub fn derive_response(input: TokenStream) -> TokenStream {
     };
 
     output.into()
-}
\ No newline at end of file
+}
+
+/// Declares the route of a handler next to it, mounted with `routes!`:
+///
+/// ```ignore
+/// #[get("/users/{id}")]
+/// fn find_user(Path(id): Path<u64>) -> ResponseEntity { ... }
+///
+/// server.nest("/", routes![find_user]);
+/// ```
+///
+/// The parameters of the path are checked against the handler's `Path<T>` argument at compile
+/// time. Only works on free functions, `async fn` handlers need the `async` feature of `server`.
+#[proc_macro_attribute]
+pub fn get(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("get", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_attribute]
+pub fn head(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("head", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_attribute]
+pub fn post(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("post", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_attribute]
+pub fn put(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("put", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_attribute]
+pub fn delete(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("delete", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_attribute]
+pub fn connect(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("connect", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_attribute]
+pub fn options(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("options", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_attribute]
+pub fn trace(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("trace", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_attribute]
+pub fn patch(attr: TokenStream, input: TokenStream) -> TokenStream {
+    route::expand("patch", attr.into(), input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+/// `Router` with the routes of handlers declared with the route attributes.
+#[proc_macro]
+pub fn routes(input: TokenStream) -> TokenStream {
+    route::expand_routes(input.into()).unwrap_or_else(|err| err.to_compile_error()).into()
+}
@@ -0,0 +1,279 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{punctuated::Punctuated, spanned::Spanned, FnArg, GenericArgument, ItemFn, LitStr, Pat, PathArguments, Token, Type};
+
+/// Primitive types a single path parameter can be extracted as with `Path<T>`.
+const PRIMITIVES: [&str; 18] = [
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+    "f32", "f64", "bool", "char", "String", "str"
+];
+
+/// Map types taking every path parameter by name, whatever they are.
+const MAPS: [&str; 2] = ["HashMap", "BTreeMap"];
+
+/// Keeps the handler as it is and adds the function `routes!` calls to mount it.
+pub(crate) fn expand(method: &str, attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    let path: LitStr = syn::parse2(attr)?;
+    let func: ItemFn = syn::parse2(item)?;
+
+    let assertions = check_params(&path, &func)?;
+
+    let ident = &func.sig.ident;
+    let vis = &func.vis;
+    let route = route_ident(ident);
+    let method = format_ident!("{}", method.to_uppercase());
+    let mount = match func.sig.asyncness {
+        Some(_) => quote!(mount_async),
+        None => quote!(mount),
+    };
+
+    Ok(quote! {
+        #func
+
+        #assertions
+
+        #[doc(hidden)]
+        #vis fn #route(router: &mut server::router::Router) {
+            router.#mount((server::model::enums::method::Method::#method, #path.to_string(), #ident));
+        }
+    })
+}
+
+/// Builds a `Router` with the routes of every listed handler.
+pub(crate) fn expand_routes(input: TokenStream) -> syn::Result<TokenStream> {
+    let handlers = syn::parse::Parser::parse2(Punctuated::<syn::Path, Token![,]>::parse_terminated, input)?;
+
+    let routes = handlers.into_iter().map(|mut handler| {
+        if let Some(last) = handler.segments.last_mut() {
+            last.ident = route_ident(&last.ident);
+        }
+
+        handler
+    });
+
+    Ok(quote! {
+        {
+            let mut router = server::router::Router::new();
+            #( #routes(&mut router); )*
+            router
+        }
+    })
+}
+
+fn route_ident(ident: &Ident) -> Ident {
+    format_ident!("__{}_route", ident)
+}
+
+/// Names of the parameters in a route path, `{id}`, `{id:u64}` and `{*rest}` all name a parameter.
+fn param_names(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+        .map(|param| {
+            let param = param.strip_prefix('*').unwrap_or(param);
+            param.split(':').next().unwrap_or_default().to_string()
+        })
+        .collect()
+}
+
+/// Checks that the handler takes the parameters of its path, either with a `Path<T>` matching
+/// them or with the `HashMap<String, String>` of every parameter.
+///
+/// Whether a struct has a field for every parameter can't be known here, so for structs it
+/// returns an assertion that fails to compile when one of them is missing.
+fn check_params(path: &LitStr, func: &ItemFn) -> syn::Result<TokenStream> {
+    let names = param_names(&path.value());
+    let mut taken = false;
+    let mut assertions = TokenStream::new();
+
+    for arg in &func.sig.inputs {
+        let arg = match arg {
+            FnArg::Typed(arg) => arg,
+            FnArg::Receiver(receiver) => return Err(syn::Error::new_spanned(receiver, "route attributes only work on free functions")),
+        };
+
+        match last_segment(&arg.ty) {
+            Some((name, inner)) if name == "Path" => {
+                taken = true;
+
+                if let Some(unbound) = check_path_arg(path, &names, &arg.pat, inner)? {
+                    // Generic structs can't be named outside the handler
+                    if func.sig.generics.params.is_empty() {
+                        assertions.extend(assert_fields(inner, &unbound));
+                    }
+                }
+            },
+            Some((name, _)) if name == "HashMap" => taken = true,
+            _ => {},
+        }
+    }
+
+    match names.first() {
+        Some(name) if !taken => Err(syn::Error::new_spanned(
+            &func.sig.ident,
+            format!("path parameter `{name}` of \"{}\" isn't used by the handler, take it with `Path<T>`", path.value())
+        )),
+        _ => Ok(assertions),
+    }
+}
+
+/// Checks a `Path<T>` argument against the parameters of the path. Returns the parameters a
+/// struct `T` has to have fields for but that the pattern doesn't bind.
+fn check_path_arg(path: &LitStr, names: &[String], pat: &Pat, inner: Option<&Type>) -> syn::Result<Option<Vec<String>>> {
+    let inner = match inner {
+        Some(inner) => inner,
+        None => return Ok(None),
+    };
+
+    if names.is_empty() {
+        return Err(syn::Error::new_spanned(inner, format!("\"{}\" has no path parameters", path.value())));
+    }
+
+    let expected = match inner {
+        Type::Tuple(tuple) => Some(tuple.elems.len()),
+        Type::Reference(_) => Some(1),
+        Type::Path(_) => match last_segment(inner) {
+            Some((name, _)) if PRIMITIVES.contains(&name.as_str()) => Some(1),
+            Some((name, _)) if MAPS.contains(&name.as_str()) => return Ok(None),
+            _ => None,
+        },
+        _ => return Ok(None),
+    };
+
+    if let Some(expected) = expected {
+        if expected != names.len() {
+            return Err(syn::Error::new_spanned(
+                inner,
+                format!("\"{}\" has {} path parameters but the handler takes {expected}", path.value(), names.len())
+            ));
+        }
+
+        return Ok(None);
+    }
+
+    let fields = match pat {
+        Pat::TupleStruct(outer) => match outer.pat.elems.first() {
+            Some(Pat::Struct(fields)) => fields,
+            _ => return Ok(Some(names.to_vec())),
+        },
+        _ => return Ok(Some(names.to_vec())),
+    };
+
+    // Fields destructured from the struct, as in `Path(UserPath { id, .. })`, have to be in the path
+    let mut bound = vec![];
+
+    for field in &fields.fields {
+        if let syn::Member::Named(name) = &field.member {
+            if !names.iter().any(|n| name == n) {
+                return Err(syn::Error::new_spanned(name, format!("\"{}\" has no path parameter `{name}`", path.value())));
+            }
+
+            bound.push(name.to_string());
+        }
+    }
+
+    let unbound: Vec<String> = names.iter().filter(|n| !bound.contains(n)).cloned().collect();
+
+    match (unbound.first(), &fields.dot2_token) {
+        (Some(name), None) => Err(syn::Error::new_spanned(
+            fields,
+            format!("path parameter `{name}` of \"{}\" isn't bound by the pattern", path.value())
+        )),
+        _ => Ok(Some(unbound)),
+    }
+}
+
+/// Code that only compiles when the struct `ty` has a field for each of `names`.
+fn assert_fields(ty: Option<&Type>, names: &[String]) -> TokenStream {
+    // Names that aren't identifiers can't be fields, the parameter is renamed with serde then
+    let ty = match ty {
+        Some(ty) => ty,
+        None => return TokenStream::new(),
+    };
+
+    let fields: Vec<Ident> = names.iter()
+        .filter(|name| syn::parse_str::<Ident>(name).is_ok())
+        .map(|name| Ident::new(name, ty.span()))
+        .collect();
+
+    match fields.is_empty() {
+        false => quote_spanned! {ty.span()=>
+            const _: () = {
+                #[allow(dead_code)]
+                fn path_params(params: &#ty) {
+                    let _ = (#(&params.#fields,)*);
+                }
+            };
+        },
+        true => TokenStream::new(),
+    }
+}
+
+/// Name of the last segment of a type path along with its first generic type, `Path` and
+/// `(u64,)` for `extract::Path<(u64,)>`.
+fn last_segment(ty: &Type) -> Option<(String, Option<&Type>)> {
+    let segment = match ty {
+        Type::Path(ty) => ty.path.segments.last()?,
+        _ => return None,
+    };
+
+    let inner = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    };
+
+    Some((segment.ident.to_string(), inner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn check(path: &str, func: ItemFn) -> syn::Result<TokenStream> {
+        check_params(&LitStr::new(path, proc_macro2::Span::call_site()), &func)
+    }
+
+    fn error(path: &str, func: ItemFn) -> String {
+        match check(path, func) {
+            Ok(_) => panic!("expected {path} to be rejected"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn names_path_params() {
+        assert_eq!(param_names("/users/{id}/posts/{post:u64}/{*rest}"), ["id", "post", "rest"]);
+        assert!(param_names("/users/me").is_empty());
+    }
+
+    #[test]
+    fn accepts_handlers_taking_every_param() {
+        assert!(check("/users/{id}", parse_quote!(fn a(Path(id): Path<u64>) {})).unwrap().is_empty());
+        assert!(check("/users/{id}/{post}", parse_quote!(fn a(Path((id, post)): Path<(u64, String)>) {})).is_ok());
+        assert!(check("/users/{id}", parse_quote!(fn a(_headers: HeaderMap, params: HashMap<String, String>) {})).is_ok());
+        assert!(check("/users/{id}/{post}", parse_quote!(fn a(Path(UserPost { id, post }): Path<UserPost>) {})).unwrap().is_empty());
+        assert!(check("/users", parse_quote!(fn a(Json(user): Json<User>) {})).is_ok());
+    }
+
+    #[test]
+    fn asserts_struct_fields_that_are_not_bound() {
+        let assertions = check("/users/{id}/{post}", parse_quote!(fn a(Path(path): Path<UserPost>) {})).unwrap().to_string();
+        assert!(assertions.contains("& params . id") && assertions.contains("& params . post"));
+
+        let assertions = check("/users/{id}/{post}", parse_quote!(fn a(Path(UserPost { id, .. }): Path<UserPost>) {})).unwrap().to_string();
+        assert!(!assertions.contains("& params . id") && assertions.contains("& params . post"));
+    }
+
+    #[test]
+    fn rejects_handlers_missing_params() {
+        assert!(error("/users/{id}", parse_quote!(fn a() {})).contains("path parameter `id`"));
+        assert!(error("/users/{id}/{post}", parse_quote!(fn a(Path((id,)): Path<(u64,)>) {})).contains("has 2 path parameters but the handler takes 1"));
+        assert!(error("/users/{id}/{post}", parse_quote!(fn a(Path(id): Path<u64>) {})).contains("has 2 path parameters"));
+        assert!(error("/users/{id}", parse_quote!(fn a(Path(UserPost { id, post }): Path<UserPost>) {})).contains("no path parameter `post`"));
+        assert!(error("/users/{id}/{post}", parse_quote!(fn a(Path(UserPost { id }): Path<UserPost>) {})).contains("`post` of \"/users/{id}/{post}\" isn't bound"));
+        assert!(error("/users", parse_quote!(fn a(Path(id): Path<u64>) {})).contains("has no path parameters"));
+    }
+}
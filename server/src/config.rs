@@ -0,0 +1,133 @@
+use std::{env, str::FromStr, thread, time::Duration};
+
+const DEFAULT_MAX_HEADER_SIZE: usize = 8 * 1024;
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+const DEFAULT_QUEUE_SIZE: usize = 128;
+const DEFAULT_KEEP_ALIVE_TIMEOUT: u64 = 5;
+const DEFAULT_REQUEST_TIMEOUT: u64 = 10;
+const DEFAULT_MAX_REQUESTS_PER_CONNECTION: usize = 100;
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+
+/// Settings used by the `Server` while handling connections.
+///
+/// `ServerConfig::from_env` reads the values from the environment (and from a `.env` file if
+/// there is one), any value that isn't set falls back to its default.
+///
+/// | Variable                  | Default              |
+/// |---------------------------|----------------------|
+/// | `SERVER_MAX_HEADER_SIZE`  | 8 KiB                |
+/// | `SERVER_MAX_BODY_SIZE`    | 1 MiB                |
+/// | `SERVER_WORKERS`          | 2 × CPUs, at least 4 |
+/// | `SERVER_QUEUE_SIZE`       | 128                  |
+/// | `SERVER_KEEP_ALIVE`       | 5 (seconds)          |
+/// | `SERVER_REQUEST_TIMEOUT`  | 10 (seconds)         |
+/// | `SERVER_MAX_REQUESTS`     | 100                  |
+/// | `SERVER_SHUTDOWN_TIMEOUT` | 30 (seconds)         |
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub(in crate) max_header_size: usize,
+    pub(in crate) max_body_size: usize,
+    pub(in crate) workers: usize,
+    pub(in crate) queue_size: usize,
+    pub(in crate) keep_alive_timeout: Duration,
+    pub(in crate) request_timeout: Duration,
+    pub(in crate) max_requests_per_connection: usize,
+    pub(in crate) shutdown_timeout: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            workers: thread::available_parallelism().map(|n| n.get() * 2).unwrap_or(1).max(4),
+            queue_size: DEFAULT_QUEUE_SIZE,
+            keep_alive_timeout: Duration::from_secs(DEFAULT_KEEP_ALIVE_TIMEOUT),
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT),
+            max_requests_per_connection: DEFAULT_MAX_REQUESTS_PER_CONNECTION,
+            shutdown_timeout: Duration::from_secs(DEFAULT_SHUTDOWN_TIMEOUT),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+
+        let default = Self::default();
+
+        Self {
+            max_header_size: env_or("SERVER_MAX_HEADER_SIZE", default.max_header_size),
+            max_body_size: env_or("SERVER_MAX_BODY_SIZE", default.max_body_size),
+            workers: env_or("SERVER_WORKERS", default.workers),
+            queue_size: env_or("SERVER_QUEUE_SIZE", default.queue_size),
+            keep_alive_timeout: Duration::from_secs(env_or("SERVER_KEEP_ALIVE", DEFAULT_KEEP_ALIVE_TIMEOUT)),
+            request_timeout: Duration::from_secs(env_or("SERVER_REQUEST_TIMEOUT", DEFAULT_REQUEST_TIMEOUT)),
+            max_requests_per_connection: env_or("SERVER_MAX_REQUESTS", default.max_requests_per_connection),
+            shutdown_timeout: Duration::from_secs(env_or("SERVER_SHUTDOWN_TIMEOUT", DEFAULT_SHUTDOWN_TIMEOUT)),
+        }
+    }
+
+    /// Maximum size in bytes of the request line plus headers, larger requests get a 431.
+    pub fn with_max_header_size(self, max_header_size: usize) -> Self {
+        Self { max_header_size, ..self }
+    }
+
+    /// Maximum size in bytes of a request body, larger requests get a 413.
+    pub fn with_max_body_size(self, max_body_size: usize) -> Self {
+        Self { max_body_size, ..self }
+    }
+
+    /// Amount of threads handling connections concurrently.
+    pub fn with_workers(self, workers: usize) -> Self {
+        Self { workers: workers.max(1), ..self }
+    }
+
+    /// Amount of accepted connections that can wait for a free worker, once the queue is full
+    /// the server stops accepting until a worker picks one of them up.
+    pub fn with_queue_size(self, queue_size: usize) -> Self {
+        Self { queue_size, ..self }
+    }
+
+    /// How long an open connection can stay idle waiting for the next request before it's closed.
+    /// Idle connections are closed sooner when other connections are waiting for a worker.
+    pub fn with_keep_alive_timeout(self, keep_alive_timeout: Duration) -> Self {
+        Self { keep_alive_timeout, ..self }
+    }
+
+    /// How long a client has to send a whole request, head and body, once it started it (or
+    /// once it connected for the first request), slower clients get a 408.
+    pub fn with_request_timeout(self, request_timeout: Duration) -> Self {
+        Self { request_timeout, ..self }
+    }
+
+    /// Amount of requests served on a single connection, the last one is answered with
+    /// `Connection: close`.
+    pub fn with_max_requests_per_connection(self, max_requests_per_connection: usize) -> Self {
+        Self { max_requests_per_connection: max_requests_per_connection.max(1), ..self }
+    }
+
+    /// How long a shutdown waits for the requests being handled before the server stops anyway.
+    pub fn with_shutdown_timeout(self, shutdown_timeout: Duration) -> Self {
+        Self { shutdown_timeout, ..self }
+    }
+}
+
+fn env_or<T>(key: &str, default: T) -> T
+    where T: FromStr
+{
+    match env::var(key) {
+        Ok(value) => match value.trim().parse::<T>() {
+            Ok(v) => v,
+            Err(_) => {
+                println!("Invalid value {value:?} for {key}, using the default");
+                default
+            },
+        },
+        Err(_) => default,
+    }
+}
@@ -0,0 +1,194 @@
+use std::{any::{Any, TypeId}, collections::HashMap, sync::Arc};
+
+use serde::de::DeserializeOwned;
+
+use crate::{model::{Request, request::RequestObj, header_map::HeaderMap, query_map::QueryMap, api_error::ApiError}, server_utils::params::ParamsDeserializer};
+
+/// Values shared with every handler through the `State` extractor, keyed by their type.
+pub(in crate) type StateMap = HashMap<TypeId, Box<dyn Any + Send + Sync>>;
+
+/// Everything known about a request once it was routed, extractors build their values from it.
+pub struct RequestContext {
+    pub(in crate) request: RequestObj<Vec<u8>>,
+    pub(in crate) path_params: Vec<(String, String)>,
+    pub(in crate) state: Arc<StateMap>,
+}
+
+impl RequestContext {
+    pub(in crate) fn new(request: RequestObj<Vec<u8>>, path_params: Vec<(String, String)>, state: Arc<StateMap>) -> Self {
+        Self { request, path_params, state }
+    }
+
+    /// Sets the path parameters once the request is routed.
+    pub(in crate) fn with_path_params(self, path_params: Vec<(String, String)>) -> Self {
+        Self { path_params, ..self }
+    }
+
+    pub fn request(&self) -> &RequestObj<Vec<u8>> {
+        &self.request
+    }
+
+    /// Path parameters in the order they appear in the mounted path. Global middlewares run
+    /// before the request is routed, so they always see them empty.
+    pub fn path_params(&self) -> &[(String, String)] {
+        &self.path_params
+    }
+
+    /// Raw query string, without the `?` and the fragment.
+    pub fn query(&self) -> Option<&str> {
+        let target = self.request.path.split('#').next().unwrap_or_default();
+        target.split_once('?').map(|(_, query)| query)
+    }
+
+    /// Decoded query parameters, see `QueryMap`.
+    pub fn query_params(&self) -> QueryMap {
+        self.query().map(QueryMap::parse).unwrap_or_default()
+    }
+
+    /// State registered with `Server::with_state`.
+    pub fn state<T>(&self) -> Option<&T>
+        where T: Send + Sync + 'static
+    {
+        self.state.get(&TypeId::of::<T>()).and_then(|s| s.downcast_ref())
+    }
+}
+
+/// Types a handler can take as arguments, each one is built from the request before the
+/// handler is called. When one fails the handler isn't called and the error is returned.
+pub trait FromRequest: Sized {
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError>;
+}
+
+/// Path parameters deserialized by position into a tuple (`Path<(u64,)>`), by name into a
+/// struct or as a single value (`Path<u64>`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path<T>(pub T);
+
+/// Query parameters deserialized into a struct by name, `Vec` fields receive every value
+/// of a repeated name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+/// JSON request body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+/// Copy of the value registered with `Server::with_state`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State<T>(pub T);
+
+impl<T> FromRequest for Path<T>
+    where T: DeserializeOwned
+{
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+        T::deserialize(ParamsDeserializer::new(&context.path_params))
+            .map(Path)
+            .map_err(|err| ApiError::bad_request(String::from("Invalid path parameters"), format!("Failed to parse path parameters: {err}")))
+    }
+}
+
+impl<T> FromRequest for Query<T>
+    where T: DeserializeOwned
+{
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+        let params = context.query_params();
+
+        T::deserialize(ParamsDeserializer::new(params.pairs()))
+            .map(Query)
+            .map_err(|err| ApiError::bad_request(String::from("Invalid query parameters"), format!("Failed to parse query parameters: {err}")))
+    }
+}
+
+impl<T> FromRequest for Json<T>
+    where T: DeserializeOwned
+{
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+        Ok(Json(serde_json::from_slice(&context.request.body)?))
+    }
+}
+
+impl<T> FromRequest for State<T>
+    where T: Clone + Send + Sync + 'static
+{
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+        match context.state::<T>() {
+            Some(state) => Ok(State(state.clone())),
+            None => Err(ApiError::internal_error(
+                String::from("Missing server state"),
+                format!("No state of type {} was registered with Server::with_state", std::any::type_name::<T>())
+            )),
+        }
+    }
+}
+
+impl FromRequest for HeaderMap {
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+        Ok(context.request.headers.clone())
+    }
+}
+
+impl FromRequest for QueryMap {
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+        Ok(context.query_params())
+    }
+}
+
+/// Path parameters by name, query parameters are received with `Query<T>` or `QueryMap`.
+impl FromRequest for HashMap<String, String> {
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+        Ok(context.path_params.iter().cloned().collect())
+    }
+}
+
+/// Body of the request built with `Request::string_body_to_obj`.
+impl<T> FromRequest for T
+    where T: Request
+{
+    fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+        T::string_body_to_obj(String::from_utf8_lossy(&context.request.body).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_derive::Deserialize;
+    use crate::model::enums::method::Method;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Filter {
+        name: String,
+        limit: Option<u32>
+    }
+
+    fn context(path: &str, path_params: &[(&str, &str)], body: &str) -> RequestContext {
+        let request = RequestObj::new(path.to_string(), Method::GET, String::from("HTTP/1.1"), HeaderMap::new(), body.as_bytes().to_vec());
+        let path_params = path_params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+
+        RequestContext::new(request, path_params, Arc::new(StateMap::new()))
+    }
+
+    #[test]
+    fn extracts_path_and_query() {
+        let context = context("/users/7?name=ferris&limit=10", &[("id", "7")], "");
+
+        assert_eq!(Path::<(u64,)>::from_request(&context).unwrap(), Path((7,)));
+        assert_eq!(Query::<Filter>::from_request(&context).unwrap(), Query(Filter { name: String::from("ferris"), limit: Some(10) }));
+
+        let params = HashMap::<String, String>::from_request(&context).unwrap();
+        assert_eq!(params, HashMap::from([(String::from("id"), String::from("7"))]));
+
+        let query = QueryMap::from_request(&context).unwrap();
+        assert_eq!(query.get("name"), Some("ferris"));
+    }
+
+    #[test]
+    fn failed_extractions_are_bad_requests() {
+        let context = context("/users/me", &[("id", "me")], "{ \"name\": ");
+
+        assert_eq!(Path::<u64>::from_request(&context).unwrap_err().status().status_number(), 400);
+        assert_eq!(Query::<Filter>::from_request(&context).unwrap_err().status().status_number(), 400);
+        assert_eq!(Json::<Filter>::from_request(&context).unwrap_err().status().status_number(), 400);
+        assert_eq!(State::<u64>::from_request(&context).unwrap_err().status().status_number(), 500);
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin};
+
+use crate::{extract::{FromRequest, RequestContext}, model::{response_entity::ResponseEntity, api_error::ApiError, into_response::IntoResponse}};
+
+/// Type erased handler stored in the route table, every mounted function is wrapped into one
+/// so routes with different request bodies can live in the same `Server`.
+pub(in crate) type BoxedHandler = Arc<dyn Fn(RequestContext) -> ResponseEntity + Send + Sync>;
+
+#[cfg(feature = "async")]
+pub(in crate) type BoxedFuture = Pin<Box<dyn Future<Output = ResponseEntity> + Send>>;
+
+#[cfg(feature = "async")]
+pub(in crate) type BoxedAsyncHandler = Arc<dyn Fn(RequestContext) -> BoxedFuture + Send + Sync>;
+
+/// Functions that can be mounted on a `Server`.
+///
+/// It's implemented for functions taking up to eight arguments that implement `FromRequest`,
+/// in any order, and returning an `IntoResponse` value:
+///
+/// ```ignore
+/// fn find_user(Path((id,)): Path<(u64,)>, Query(filter): Query<Filter>) -> Result<ResponseEntity, ApiError>
+/// fn create_user(headers: HeaderMap, Json(user): Json<CreateUser>, State(db): State<Db>) -> ResponseEntity
+/// fn hello(headers: HeaderMap, params: HashMap<String, String>, req: HelloRequest) -> ResponseEntity
+/// fn ping() -> &'static str
+/// fn find_post(Path(id): Path<u64>) -> Option<(StatusCode, Post)>
+/// ```
+///
+/// `Args` only exists to tell the implementations apart, it's always inferred.
+pub trait Handler<Args>: Send + Sync + 'static {
+    fn call(&self, context: RequestContext) -> ResponseEntity;
+}
+
+/// Async counterpart of `Handler`, implemented for `async fn` with the same arguments.
+#[cfg(feature = "async")]
+pub trait AsyncHandler<Args>: Send + Sync + 'static {
+    fn call(&self, context: RequestContext) -> BoxedFuture;
+}
+
+macro_rules! impl_handler {
+    ($($arg:ident),*) => {
+        // Extracts every argument in order, stopping at the first failure
+        #[allow(unused_variables)]
+        impl<$($arg,)*> FromRequest for ($($arg,)*)
+            where $($arg: FromRequest,)*
+        {
+            fn from_request(context: &RequestContext) -> Result<Self, ApiError> {
+                Ok(($($arg::from_request(context)?,)*))
+            }
+        }
+
+        #[allow(non_snake_case, unused_variables)]
+        impl<F, R, $($arg,)*> Handler<(R, $($arg,)*)> for F
+            where F: Fn($($arg),*) -> R + Send + Sync + 'static,
+                  R: IntoResponse,
+                  $($arg: FromRequest,)*
+        {
+            fn call(&self, context: RequestContext) -> ResponseEntity {
+                let extracted = <($($arg,)*)>::from_request(&context);
+
+                match extracted {
+                    Ok(($($arg,)*)) => self($($arg),*).into_response(),
+                    Err(err) => ResponseEntity::from(err),
+                }
+            }
+        }
+
+        #[cfg(feature = "async")]
+        #[allow(non_snake_case, unused_variables)]
+        impl<F, Fut, $($arg,)*> AsyncHandler<(Fut, $($arg,)*)> for F
+            where F: Fn($($arg),*) -> Fut + Send + Sync + 'static,
+                  Fut: Future + Send + 'static,
+                  Fut::Output: IntoResponse,
+                  $($arg: FromRequest,)*
+        {
+            fn call(&self, context: RequestContext) -> BoxedFuture {
+                let extracted = <($($arg,)*)>::from_request(&context);
+
+                match extracted {
+                    Ok(($($arg,)*)) => {
+                        let future = self($($arg),*);
+                        Box::pin(async move { future.await.into_response() })
+                    },
+                    Err(err) => {
+                        let response = ResponseEntity::from(err);
+                        Box::pin(async move { response })
+                    },
+                }
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(T1);
+impl_handler!(T1, T2);
+impl_handler!(T1, T2, T3);
+impl_handler!(T1, T2, T3, T4);
+impl_handler!(T1, T2, T3, T4, T5);
+impl_handler!(T1, T2, T3, T4, T5, T6);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7, T8);
+
+pub(in crate) fn boxed<H, Args>(handler: H) -> BoxedHandler
+    where H: Handler<Args>
+{
+    Arc::new(move |context| handler.call(context))
+}
+
+#[cfg(feature = "async")]
+pub(in crate) fn boxed_async<H, Args>(handler: H) -> BoxedAsyncHandler
+    where H: AsyncHandler<Args>
+{
+    Arc::new(move |context| handler.call(context))
+}
@@ -1,4 +1,10 @@
 pub mod server;
+pub mod config;
+pub mod shutdown;
+pub mod handler;
+pub mod extract;
+pub mod router;
+pub mod middleware;
 pub mod model;
 pub mod macros;
 pub(in crate) mod server_utils;
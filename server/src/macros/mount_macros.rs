@@ -1,62 +1,131 @@
+/// Route for `Server::mount` and `Router::mount`, optionally followed by middlewares that only
+/// run for this route, the first one outermost:
+///
+/// ```ignore
+/// server.mount(get_route!["/users/{id}", find_user]);
+/// server.mount(get_route!["/admin", admin_panel, require_auth, log_request]);
+/// ```
+///
+/// An `async fn` handler goes to `mount_async` on its own, with middlewares it's marked with
+/// `async` and mounted with `mount` (this needs the `async` feature):
+///
+/// ```ignore
+/// server.mount_async(get_route!["/users/{id}", find_user_async]);
+/// server.mount(get_route![async "/admin", admin_panel_async, require_auth]);
+/// ```
 #[macro_export]
-macro_rules! get {
+macro_rules! get_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::GET, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::GET, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::GET, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
 
 #[macro_export]
-macro_rules! head {
+macro_rules! head_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::HEAD, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::HEAD, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::HEAD, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
 
 #[macro_export]
-macro_rules! post {
+macro_rules! post_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::POST, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::POST, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::POST, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
 
 #[macro_export]
-macro_rules! put {
+macro_rules! put_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::PUT, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::PUT, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::PUT, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
 
 #[macro_export]
-macro_rules! delete {
+macro_rules! delete_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::DELETE, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::DELETE, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::DELETE, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
 
 #[macro_export]
-macro_rules! connect {
+macro_rules! connect_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::CONNECT, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::CONNECT, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::CONNECT, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
 
 #[macro_export]
-macro_rules! options {
+macro_rules! options_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::OPTIONS, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::OPTIONS, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::OPTIONS, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
 
 #[macro_export]
-macro_rules! trace {
+macro_rules! trace_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::TRACE, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::TRACE, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::TRACE, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
 
 #[macro_export]
-macro_rules! patch {
+macro_rules! patch_route {
+    (async $a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::PATCH, $a.to_string(), server::middleware::layer_async($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
     ($a:expr, $b:expr) => {
         (server::model::enums::method::Method::PATCH, $a.to_string(), $b)
     };
+    ($a:expr, $b:expr, $($m:expr),+ $(,)?) => {
+        (server::model::enums::method::Method::PATCH, $a.to_string(), server::middleware::layer($b, vec![$(std::sync::Arc::new($m) as std::sync::Arc<dyn server::middleware::Middleware>),+]))
+    };
 }
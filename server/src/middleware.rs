@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use crate::{extract::RequestContext, handler::{self, Handler, BoxedHandler}, model::response_entity::ResponseEntity, server::RouteHandler};
+#[cfg(feature = "async")]
+use crate::{handler::AsyncHandler, server_utils::async_server::block_on};
+
+/// Code that runs around a handler. It receives the request and the rest of the chain in
+/// `next`, so it can act before and after calling `next.run(context)` or answer on its own
+/// without calling it.
+///
+/// Middlewares can be added for every request with `Server::with_middleware`, for a group of
+/// routes with `Router::with_middleware` or for a single route with `layer`. It's implemented
+/// for closures taking `(RequestContext, Next)`:
+///
+/// ```ignore
+/// let logger = |context: RequestContext, next: Next| {
+///     println!("{} {}", context.request().method, context.request().path);
+///     next.run(context)
+/// };
+/// ```
+pub trait Middleware: Send + Sync + 'static {
+    fn handle(&self, context: RequestContext, next: Next) -> ResponseEntity;
+}
+
+impl<F> Middleware for F
+    where F: Fn(RequestContext, Next) -> ResponseEntity + Send + Sync + 'static
+{
+    fn handle(&self, context: RequestContext, next: Next) -> ResponseEntity {
+        self(context, next)
+    }
+}
+
+/// The middlewares left in the chain followed by the handler.
+pub struct Next {
+    handler: BoxedHandler,
+}
+
+impl Next {
+    pub fn run(self, context: RequestContext) -> ResponseEntity {
+        (self.handler)(context)
+    }
+}
+
+/// Handler with middlewares of its own, mounted like any other handler.
+///
+/// ```ignore
+/// server.mount(get_route!["/admin", admin_panel, require_auth, log_request]);
+/// // Same as
+/// server.mount(get_route!["/admin", layer(admin_panel, vec![Arc::new(require_auth), Arc::new(log_request)])]);
+/// ```
+pub struct Layered {
+    handler: BoxedHandler,
+}
+
+impl Handler<Layered> for Layered {
+    fn call(&self, context: RequestContext) -> ResponseEntity {
+        (self.handler)(context)
+    }
+}
+
+/// Runs `middlewares` around `handler`, the first one is the outermost.
+pub fn layer<H, Args>(handler: H, middlewares: Vec<Arc<dyn Middleware>>) -> Layered
+    where H: Handler<Args>
+{
+    Layered { handler: chain(&middlewares, handler::boxed(handler)) }
+}
+
+/// Same as `layer` for an `async fn` handler, its future is driven to completion by the chain.
+#[cfg(feature = "async")]
+pub fn layer_async<H, Args>(handler: H, middlewares: Vec<Arc<dyn Middleware>>) -> Layered
+    where H: AsyncHandler<Args>
+{
+    Layered { handler: chain(&middlewares, into_sync(RouteHandler::Async(handler::boxed_async(handler)))) }
+}
+
+/// Puts `middlewares` in front of `endpoint`, the first one is the outermost.
+pub(in crate) fn chain(middlewares: &[Arc<dyn Middleware>], endpoint: BoxedHandler) -> BoxedHandler {
+    middlewares.iter().rev().fold(endpoint, |handler, middleware| {
+        let middleware = Arc::clone(middleware);
+        Arc::new(move |context| middleware.handle(context, Next { handler: Arc::clone(&handler) }))
+    })
+}
+
+/// Puts `middlewares` in front of `handler`. Async handlers become sync ones driving the
+/// handler's future, since the chain itself is sync.
+pub(in crate) fn wrap(handler: RouteHandler, middlewares: &[Arc<dyn Middleware>]) -> RouteHandler {
+    match middlewares.is_empty() {
+        true => handler,
+        false => RouteHandler::Sync(chain(middlewares, into_sync(handler))),
+    }
+}
+
+fn into_sync(handler: RouteHandler) -> BoxedHandler {
+    match handler {
+        RouteHandler::Sync(handler) => handler,
+        #[cfg(feature = "async")]
+        RouteHandler::Async(handler) => Arc::new(move |context| block_on(handler(context))),
+    }
+}
@@ -2,11 +2,16 @@ use serde::{Deserialize, Serialize};
 
 pub(in crate) mod request;
 pub mod enums;
-pub(in crate) mod response;
 pub mod response_entity;
+pub mod header_map;
+pub mod query_map;
+pub mod api_error;
+pub mod into_response;
 
 pub trait Request: Serialize + Deserialize<'static> + Clone { 
-    fn string_body_to_obj(body: String) -> Self
+    /// Builds the request object from the body, a failure is sent to the client as the
+    /// returned error instead of calling the handler.
+    fn string_body_to_obj(body: String) -> Result<Self, api_error::ApiError>
         where Self: Serialize + Deserialize<'static> + Sized + Clone;
 }
 
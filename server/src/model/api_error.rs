@@ -0,0 +1,160 @@
+use std::{collections::HashMap, fmt::{Formatter, Display, Result as FmtResult}};
+
+use serde::Serialize;
+use serde_derive::Serialize;
+use serde_json::{Map, Value};
+
+use super::{enums::status_code::StatusCode, response_entity::ResponseEntity};
+
+/// Error sent to the client as an RFC 7807 problem, with `Content-Type: application/problem+json`:
+///
+/// ```json
+/// {
+///   "type": "https://example.com/problems/out-of-credit",
+///   "title": "You do not have enough credit",
+///   "status": 403,
+///   "detail": "Your current balance is 30, but that costs 50",
+///   "instance": "/account/12345/msgs/abc",
+///   "balance": 30
+/// }
+/// ```
+///
+/// Every error the server answers with on its own has this shape too. Handlers returning
+/// `Result<T, E>` work with any `E: Into<ApiError>`, so application errors only need a `From`
+/// implementation to be usable with `?`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct ApiError(Box<Problem>);
+
+// Boxed so `Result<T, ApiError>` stays small
+#[derive(Debug, Clone, Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: String,
+    status: StatusCode,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<String>,
+    #[serde(flatten)]
+    extensions: Map<String, Value>
+}
+
+impl ApiError {
+    /// Problem of the default `about:blank` type, `title` should summarize it and `detail`
+    /// explain this occurrence.
+    pub fn new(status: StatusCode, title: String, detail: String) -> Self {
+        Self(Box::new(Problem { problem_type: String::from("about:blank"), title, status, detail, instance: None, extensions: Map::new() }))
+    }
+
+    /// Problem titled with the reason phrase of `status`, as RFC 7807 recommends for
+    /// `about:blank` problems.
+    pub fn from_status(status: StatusCode, detail: String) -> Self {
+        Self::new(status.clone(), status.reason_phrase().to_string(), detail)
+    }
+
+    pub fn bad_request(title: String, detail: String) -> Self {
+        Self::new(StatusCode::BadRequest, title, detail)
+    }
+
+    pub fn not_found(title: String, detail: String) -> Self {
+        Self::new(StatusCode::NotFound, title, detail)
+    }
+
+    pub fn internal_error(title: String, detail: String) -> Self {
+        Self::new(StatusCode::InternalServerError, title, detail)
+    }
+
+    /// URI identifying the kind of problem, ideally pointing to its documentation.
+    pub fn with_type(mut self, problem_type: &str) -> Self {
+        self.0.problem_type = problem_type.to_string();
+        self
+    }
+
+    /// URI identifying this occurrence of the problem, like the request path.
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        self.0.instance = Some(instance.to_string());
+        self
+    }
+
+    /// Adds an extension member sent next to the standard ones. Values that can't be
+    /// serialized are sent as `null`.
+    pub fn with_extension<V>(mut self, name: &str, value: V) -> Self
+        where V: Serialize
+    {
+        self.0.extensions.insert(name.to_string(), serde_json::to_value(value).unwrap_or_default());
+        self
+    }
+
+    pub fn status(&self) -> &StatusCode {
+        &self.0.status
+    }
+
+    pub fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    pub fn detail(&self) -> &str {
+        &self.0.detail
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} {}: {}", self.0.status.status_number(), self.0.title, self.0.detail)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::bad_request(String::from("Invalid request body"), format!("Failed to parse request body: {err}"))
+    }
+}
+
+impl From<ApiError> for ResponseEntity {
+    fn from(err: ApiError) -> Self {
+        println!("Returning error: {}", err);
+
+        let headers = HashMap::from([(String::from("Content-Type"), String::from("application/problem+json"))]);
+        let body = serde_json::to_string_pretty(&err).unwrap_or_default();
+
+        ResponseEntity::new(Some(body), None, headers, err.0.status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_errors_become_bad_requests() {
+        let err: ApiError = serde_json::from_str::<serde_json::Value>("{ \"name\": ").unwrap_err().into();
+        let response = ResponseEntity::from(err);
+
+        assert_eq!(response.status.status_number(), 400);
+        assert!(response.body.unwrap().contains("\"title\": \"Invalid request body\""));
+    }
+
+    #[test]
+    fn serializes_as_problem_details() {
+        let err = ApiError::from_status(StatusCode::Forbidden, String::from("Your current balance is 30, but that costs 50"))
+            .with_type("https://example.com/problems/out-of-credit")
+            .with_instance("/account/12345/msgs/abc")
+            .with_extension("balance", 30);
+
+        let response = ResponseEntity::from(err);
+        assert_eq!(response.headers.get("Content-Type"), Some(&String::from("application/problem+json")));
+
+        let body: Value = serde_json::from_str(&response.body.unwrap()).unwrap();
+        assert_eq!(body, serde_json::json!({
+            "type": "https://example.com/problems/out-of-credit",
+            "title": "Forbidden",
+            "status": 403,
+            "detail": "Your current balance is 30, but that costs 50",
+            "instance": "/account/12345/msgs/abc",
+            "balance": 30
+        }));
+    }
+}
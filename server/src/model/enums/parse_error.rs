@@ -1,10 +1,19 @@
 use std::fmt::{Formatter, Display, Result as FmtResult};
 
+use super::status_code::StatusCode;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ParseError {
     InvalidRequest,
     InvalidEncoding,
     InvalidProtocol,
     InvalidMethod,
+    InvalidHeader,
+    InvalidContentLength,
+    InvalidChunk,
+    UnsupportedTransferEncoding,
+    HeadersTooLarge,
+    BodyTooLarge,
 }
 
 impl Display for ParseError {
@@ -20,6 +29,22 @@ impl ParseError {
             Self::InvalidEncoding => "Invalid Encoding",
             Self::InvalidProtocol => "Invalid Protocol",
             Self::InvalidMethod => "Invalid Method",
+            Self::InvalidHeader => "Invalid Header",
+            Self::InvalidContentLength => "Invalid Content-Length",
+            Self::InvalidChunk => "Invalid Chunked Encoding",
+            Self::UnsupportedTransferEncoding => "Unsupported Transfer-Encoding",
+            Self::HeadersTooLarge => "Request Headers Too Large",
+            Self::BodyTooLarge => "Request Body Too Large",
+        }
+    }
+
+    /// Status code the server answers with when a request fails with this error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnsupportedTransferEncoding => StatusCode::NotImplemented,
+            Self::HeadersTooLarge => StatusCode::RequestHeaderFieldsTooLarge,
+            Self::BodyTooLarge => StatusCode::ContentTooLarge,
+            _ => StatusCode::BadRequest,
         }
     }
-}
\ No newline at end of file
+}
@@ -1,47 +1,199 @@
-use serde_derive::{Serialize, Deserialize};
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub enum StatusCode {
-    Ok,
-    Created,
-    Accepted,
-    Unauthorized,
-    Forbidden,
-    BadRequest,
-    NotFound,
-    InternalServerError,
-    NotImplemented,
-    Other((u16, String))
+use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize, Serializer};
+
+macro_rules! status_codes {
+    ($($variant:ident => ($code:literal, $phrase:literal)),* $(,)?) => {
+        /// HTTP status of a response, every code in the IANA registry has its own variant.
+        ///
+        /// It's serialized as its number, `404` instead of `"NotFound"`.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum StatusCode {
+            $($variant,)*
+            /// Valid code without a variant, only `from_u16` builds it.
+            Other(UnregisteredCode)
+        }
+
+        impl StatusCode {
+            /// Canonical reason phrase sent in the status line.
+            pub fn reason_phrase(&self) -> &str {
+                match self {
+                    $(Self::$variant => $phrase,)*
+                    Self::Other(_) => ""
+                }
+            }
+
+            pub fn status_number(&self) -> u16 {
+                match self {
+                    $(Self::$variant => $code,)*
+                    Self::Other(other) => other.0
+                }
+            }
+
+            /// Status for `code`, `None` when it's not between 100 and 599. Codes without a
+            /// variant become `Other`, sent with an empty reason phrase.
+            pub fn from_u16(code: u16) -> Option<Self> {
+                match code {
+                    $($code => Some(Self::$variant),)*
+                    100..=599 => Some(Self::Other(UnregisteredCode(code))),
+                    _ => None
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue => (100, "Continue"),
+    SwitchingProtocols => (101, "Switching Protocols"),
+    Processing => (102, "Processing"),
+    EarlyHints => (103, "Early Hints"),
+
+    Ok => (200, "OK"),
+    Created => (201, "Created"),
+    Accepted => (202, "Accepted"),
+    NonAuthoritativeInformation => (203, "Non-Authoritative Information"),
+    NoContent => (204, "No Content"),
+    ResetContent => (205, "Reset Content"),
+    PartialContent => (206, "Partial Content"),
+    MultiStatus => (207, "Multi-Status"),
+    AlreadyReported => (208, "Already Reported"),
+    ImUsed => (226, "IM Used"),
+
+    MultipleChoices => (300, "Multiple Choices"),
+    MovedPermanently => (301, "Moved Permanently"),
+    Found => (302, "Found"),
+    SeeOther => (303, "See Other"),
+    NotModified => (304, "Not Modified"),
+    UseProxy => (305, "Use Proxy"),
+    TemporaryRedirect => (307, "Temporary Redirect"),
+    PermanentRedirect => (308, "Permanent Redirect"),
+
+    BadRequest => (400, "Bad Request"),
+    Unauthorized => (401, "Unauthorized"),
+    PaymentRequired => (402, "Payment Required"),
+    Forbidden => (403, "Forbidden"),
+    NotFound => (404, "Not Found"),
+    MethodNotAllowed => (405, "Method Not Allowed"),
+    NotAcceptable => (406, "Not Acceptable"),
+    ProxyAuthenticationRequired => (407, "Proxy Authentication Required"),
+    RequestTimeout => (408, "Request Timeout"),
+    Conflict => (409, "Conflict"),
+    Gone => (410, "Gone"),
+    LengthRequired => (411, "Length Required"),
+    PreconditionFailed => (412, "Precondition Failed"),
+    ContentTooLarge => (413, "Content Too Large"),
+    UriTooLong => (414, "URI Too Long"),
+    UnsupportedMediaType => (415, "Unsupported Media Type"),
+    RangeNotSatisfiable => (416, "Range Not Satisfiable"),
+    ExpectationFailed => (417, "Expectation Failed"),
+    MisdirectedRequest => (421, "Misdirected Request"),
+    UnprocessableContent => (422, "Unprocessable Content"),
+    Locked => (423, "Locked"),
+    FailedDependency => (424, "Failed Dependency"),
+    TooEarly => (425, "Too Early"),
+    UpgradeRequired => (426, "Upgrade Required"),
+    PreconditionRequired => (428, "Precondition Required"),
+    TooManyRequests => (429, "Too Many Requests"),
+    RequestHeaderFieldsTooLarge => (431, "Request Header Fields Too Large"),
+    UnavailableForLegalReasons => (451, "Unavailable For Legal Reasons"),
+
+    InternalServerError => (500, "Internal Server Error"),
+    NotImplemented => (501, "Not Implemented"),
+    BadGateway => (502, "Bad Gateway"),
+    ServiceUnavailable => (503, "Service Unavailable"),
+    GatewayTimeout => (504, "Gateway Timeout"),
+    HttpVersionNotSupported => (505, "HTTP Version Not Supported"),
+    VariantAlsoNegotiates => (506, "Variant Also Negotiates"),
+    InsufficientStorage => (507, "Insufficient Storage"),
+    LoopDetected => (508, "Loop Detected"),
+    NotExtended => (510, "Not Extended"),
+    NetworkAuthenticationRequired => (511, "Network Authentication Required"),
+}
+
+/// Code of a `StatusCode::Other`, always between 100 and 599 and never one of the registered
+/// codes, which have their own variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnregisteredCode(u16);
+
+impl UnregisteredCode {
+    pub fn code(&self) -> u16 {
+        self.0
+    }
 }
 
 impl StatusCode {
-    pub fn reason_phrase(&self) -> &str {
-        match self {
-            Self::Ok => "Ok",
-            Self::Created => "Created",
-            Self::Accepted => "Accepted",
-            Self::Unauthorized => "Unauthorized",
-            Self::Forbidden => "Forbidden",
-            Self::BadRequest => "BadRequest",
-            Self::NotFound => "NotFound",
-            Self::InternalServerError => "Internal Server Error",
-            Self::NotImplemented => "Not Implemented",
-            Self::Other((_code, phrase)) => phrase
-        }
+    /// 1xx
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.status_number())
     }
 
-    pub fn status_number(&self) -> u16 {
-        match self {
-            Self::Ok => 200,
-            Self::Created => 201,
-            Self::Accepted => 202,
-            Self::Unauthorized => 401,
-            Self::Forbidden => 403,
-            Self::BadRequest => 400,
-            Self::NotFound => 404,
-            Self::InternalServerError => 500,
-            Self::NotImplemented => 501,
-            Self::Other((code, _phrase)) => *code
-        }
+    /// 2xx
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status_number())
+    }
+
+    /// 3xx
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.status_number())
     }
-}
\ No newline at end of file
+
+    /// 4xx
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.status_number())
+    }
+
+    /// 5xx
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.status_number())
+    }
+}
+
+impl Serialize for StatusCode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_u16(self.status_number())
+    }
+}
+
+impl<'de> Deserialize<'de> for StatusCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let code = u16::deserialize(deserializer)?;
+        Self::from_u16(code).ok_or_else(|| D::Error::custom(format!("invalid status code {code}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_canonical_phrases() {
+        assert_eq!(StatusCode::Ok.reason_phrase(), "OK");
+        assert_eq!(StatusCode::BadRequest.reason_phrase(), "Bad Request");
+        assert_eq!(StatusCode::ContentTooLarge.reason_phrase(), "Content Too Large");
+        assert_eq!(StatusCode::from_u16(404), Some(StatusCode::NotFound));
+        assert_eq!(StatusCode::from_u16(299), Some(StatusCode::Other(UnregisteredCode(299))));
+        assert_eq!(StatusCode::from_u16(299).unwrap().reason_phrase(), "");
+        assert_eq!(StatusCode::from_u16(99), None);
+        assert_eq!(StatusCode::from_u16(600), None);
+    }
+
+    #[test]
+    fn classifies_codes() {
+        assert!(StatusCode::EarlyHints.is_informational());
+        assert!(StatusCode::NoContent.is_success());
+        assert!(StatusCode::SeeOther.is_redirection());
+        assert!(StatusCode::TooManyRequests.is_client_error());
+        assert!(StatusCode::BadGateway.is_server_error());
+        assert!(!StatusCode::BadGateway.is_client_error());
+    }
+
+    #[test]
+    fn serializes_as_numbers() {
+        assert_eq!(serde_json::to_string(&StatusCode::NotFound).unwrap(), "404");
+        assert_eq!(serde_json::from_str::<StatusCode>("201").unwrap(), StatusCode::Created);
+        assert!(serde_json::from_str::<StatusCode>("1000").is_err());
+    }
+}
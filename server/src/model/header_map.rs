@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+/// Case-insensitive multimap of HTTP headers.
+///
+/// Header names are stored lowercased, so `Content-Type` and `content-type` refer to the
+/// same entry. A name can hold several values, which is the case for headers like `Accept`
+/// or `Set-Cookie` when they are sent more than once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: HashMap<String, Vec<String>>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the first value received for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .get(&name.to_ascii_lowercase())
+            .and_then(|values| values.first())
+            .map(|v| v.as_str())
+    }
+
+    /// Returns every value received for `name`, in the order they were received.
+    pub fn get_all(&self, name: &str) -> &[String] {
+        self.entries
+            .get(&name.to_ascii_lowercase())
+            .map(|values| values.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.entries.contains_key(&name.to_ascii_lowercase())
+    }
+
+    /// Sets `name` to a single value, replacing any value it had before.
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.entries.insert(name.to_ascii_lowercase(), vec![value.to_string()]);
+    }
+
+    /// Adds a value to `name`, keeping the values it already had.
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.entries
+            .entry(name.to_ascii_lowercase())
+            .or_default()
+            .push(value.to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) -> Vec<String> {
+        self.entries.remove(&name.to_ascii_lowercase()).unwrap_or_default()
+    }
+
+    /// Number of distinct header names.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|k| k.as_str())
+    }
+
+    /// Iterates over every `(name, value)` pair, yielding repeated headers once per value.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .flat_map(|(k, values)| values.iter().map(move |v| (k.as_str(), v.as_str())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_case_insensitive() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", "application/json");
+
+        assert_eq!(headers.get("content-type"), Some("application/json"));
+        assert_eq!(headers.get("CONTENT-TYPE"), Some("application/json"));
+        assert!(headers.contains_key("Content-type"));
+    }
+
+    #[test]
+    fn repeated_headers_keep_every_value() {
+        let mut headers = HeaderMap::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.append("set-cookie", "b=2");
+
+        assert_eq!(headers.get("Set-Cookie"), Some("a=1"));
+        assert_eq!(headers.get_all("Set-Cookie"), ["a=1", "b=2"]);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.iter().count(), 2);
+
+        headers.insert("Set-Cookie", "c=3");
+        assert_eq!(headers.get_all("set-cookie"), ["c=3"]);
+    }
+}
@@ -0,0 +1,128 @@
+use super::{Response, api_error::ApiError, enums::status_code::StatusCode, response_entity::{ResponseEntity, ResponseEntityBuilder}};
+
+/// Values a handler can return, they're converted into a `ResponseEntity` before being written.
+///
+/// | Returned value | Response |
+/// |----------------|----------|
+/// | `ResponseEntity` | As it is |
+/// | `StatusCode` | That status without a body |
+/// | `(StatusCode, T)` with `T: Response` | That status with `T` as the JSON body |
+/// | `String`, `&'static str` | `200 OK` with a `text/plain` body |
+/// | `()` | `200 OK` without a body |
+/// | `Option<T>` | `T`'s response, or `404 Not Found` for `None` |
+/// | `Result<T, E>` with `E: Into<ApiError>` | `T`'s response, or the error's |
+/// | `ApiError` | The error's status with its `application/problem+json` body |
+pub trait IntoResponse {
+    fn into_response(self) -> ResponseEntity;
+}
+
+impl IntoResponse for ResponseEntity {
+    fn into_response(self) -> ResponseEntity {
+        self
+    }
+}
+
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> ResponseEntity {
+        ResponseEntityBuilder::new().with_status_code(self).build().into_response()
+    }
+}
+
+impl<T> IntoResponse for (StatusCode, T)
+    where T: Response + Send + 'static
+{
+    fn into_response(self) -> ResponseEntity {
+        let (status, body) = self;
+        ResponseEntityBuilder::new().with_status_code(status).with_body(body).build().into_response()
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> ResponseEntity {
+        ResponseEntityBuilder::new()
+            .with_status_code(StatusCode::Ok)
+            .with_text_body(self)
+            .build()
+            .into_response()
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> ResponseEntity {
+        self.to_string().into_response()
+    }
+}
+
+impl IntoResponse for () {
+    fn into_response(self) -> ResponseEntity {
+        StatusCode::Ok.into_response()
+    }
+}
+
+impl<T> IntoResponse for Option<T>
+    where T: IntoResponse
+{
+    fn into_response(self) -> ResponseEntity {
+        match self {
+            Some(value) => value.into_response(),
+            None => ApiError::not_found(String::from("Resource not found"), String::from("The requested resource doesn't exist")).into_response(),
+        }
+    }
+}
+
+impl<T, E> IntoResponse for Result<T, E>
+    where T: IntoResponse, E: Into<ApiError>
+{
+    fn into_response(self) -> ResponseEntity {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(err) => err.into().into_response(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> ResponseEntity {
+        ResponseEntity::from(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_derive::Serialize;
+
+    #[derive(Serialize)]
+    struct Created {
+        id: u64
+    }
+
+    impl Response for Created {
+        fn to_string_json(&self) -> String {
+            serde_json::to_string(self).unwrap()
+        }
+    }
+
+    #[test]
+    fn converts_plain_values() {
+        let response = "pong".into_response();
+        assert_eq!(response.body.as_deref(), Some("pong"));
+        assert_eq!(response.headers.get("Content-Type"), Some(&String::from("text/plain; charset=utf-8")));
+
+        let response = (StatusCode::Created, Created { id: 7 }).into_response();
+        assert_eq!(response.status.status_number(), 201);
+        assert!(response.body.unwrap().contains("\"id\": 7"));
+
+        assert_eq!(StatusCode::Accepted.into_response().status.status_number(), 202);
+        assert!(().into_response().body.is_none());
+    }
+
+    #[test]
+    fn converts_missing_values_and_errors() {
+        assert_eq!(None::<String>.into_response().status.status_number(), 404);
+        assert_eq!(Some(StatusCode::Created).into_response().status.status_number(), 201);
+
+        let failed: Result<String, ApiError> = Err(ApiError::bad_request(String::from("Bad"), String::from("Bad input")));
+        assert_eq!(failed.into_response().status.status_number(), 400);
+    }
+}
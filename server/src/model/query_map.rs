@@ -0,0 +1,87 @@
+use crate::server_utils::server_utils::percent_decode;
+
+/// Query string parameters, decoded as `application/x-www-form-urlencoded`.
+///
+/// Parameters keep the order they were sent in and a name can hold several values, like
+/// `tag` in `?tag=a&tag=b`. Path parameters are never part of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryMap {
+    params: Vec<(String, String)>,
+}
+
+impl QueryMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a query string without the leading `?`. Empty pieces (`a=1&&b=2`) are skipped
+    /// and a name without `=` gets an empty value.
+    pub fn parse(query: &str) -> Self {
+        let params = query
+            .split('&')
+            .filter(|piece| !piece.is_empty())
+            .map(|piece| {
+                let (name, value) = piece.split_once('=').unwrap_or((piece, ""));
+                (percent_decode(name, true), percent_decode(value, true))
+            })
+            .collect();
+
+        Self { params }
+    }
+
+    /// Returns the first value sent for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Returns every value sent for `name`, in the order they were sent.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.params.iter().filter(|(k, _)| k == name).map(|(_, v)| v.as_str()).collect()
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.params.iter().any(|(k, _)| k == name)
+    }
+
+    /// Number of `(name, value)` pairs, repeated names included.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub(in crate) fn pairs(&self) -> &[(String, String)] {
+        &self.params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_form_encoded_pairs() {
+        let query = QueryMap::parse("q=rust+http%21&expr=a=1=2&flag&&name=%E2%9C%93");
+
+        assert_eq!(query.get("q"), Some("rust http!"));
+        assert_eq!(query.get("expr"), Some("a=1=2"));
+        assert_eq!(query.get("flag"), Some(""));
+        assert_eq!(query.get("name"), Some("✓"));
+        assert_eq!(query.len(), 4);
+    }
+
+    #[test]
+    fn keeps_every_value_of_repeated_names() {
+        let query = QueryMap::parse("tag=a&page=1&tag=b");
+
+        assert_eq!(query.get("tag"), Some("a"));
+        assert_eq!(query.get_all("tag"), vec!["a", "b"]);
+        assert!(QueryMap::parse("").is_empty());
+    }
+}
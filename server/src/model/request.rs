@@ -1,15 +1,15 @@
-use std::{collections::HashMap, str::FromStr};
 
 use serde::{Deserialize};
 
-use crate::model::enums::method::*;
+use crate::model::{enums::method::*, header_map::HeaderMap};
 
 #[derive(Debug)]
 pub struct RequestObj<T> 
     where T: Deserialize<'static> 
 {
     pub path: String,
-    pub headers: HashMap<String, String>,
+    pub version: String,
+    pub headers: HeaderMap,
     pub method: Method,
     pub body: T
 }
@@ -17,13 +17,35 @@ pub struct RequestObj<T>
 impl<T> RequestObj<T> 
     where T: Deserialize<'static>
 {
-    pub(in crate) fn new(path: String, method: String, headers: HashMap<String, String>, body: T) -> Self {
+    pub(in crate) fn new(path: String, method: Method, version: String, headers: HeaderMap, body: T) -> Self {
         Self {
             path,
+            version,
             headers,
-            method: Method::from_str(&method).unwrap(),
+            method,
             body: body
         }
     }
 
+    pub(in crate) fn is_http_1_0(&self) -> bool {
+        self.version == "HTTP/1.0"
+    }
+
+    /// Whether the client wants the connection to stay open after this request. HTTP/1.1
+    /// connections are persistent unless `Connection: close` is sent, HTTP/1.0 ones only when
+    /// `Connection: keep-alive` is sent.
+    pub(in crate) fn keep_alive(&self) -> bool {
+        match self.is_http_1_0() {
+            true => has_connection_option(&self.headers, "keep-alive"),
+            false => !has_connection_option(&self.headers, "close"),
+        }
+    }
+}
+
+pub(in crate) fn has_connection_option(headers: &HeaderMap, option: &str) -> bool {
+    headers
+        .get_all("Connection")
+        .iter()
+        .flat_map(|v| v.split(','))
+        .any(|o| o.trim().eq_ignore_ascii_case(option))
 }
\ No newline at end of file
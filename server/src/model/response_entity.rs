@@ -1,88 +1,358 @@
-use std::{collections::HashMap, net::TcpStream, io::Write};
+use std::{collections::HashMap, io::{self, Write}, fmt::{Debug, Formatter, Result as FmtResult}, time::SystemTime};
 
 use serde_derive::{Serialize, Deserialize};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
-use super::{enums::status_code::StatusCode, Response};
+use crate::server_utils::http_date::http_date;
 
-#[derive(Clone)]
-pub struct ResponseEntityBuilder {
-    pub(in crate) body: Option<String>,
-    pub(in crate) headers: HashMap<String, String>,
-    pub(in crate) status: StatusCode
+use super::{enums::status_code::StatusCode, api_error::ApiError, Response};
+
+/// Body that is sent to the client one chunk at a time with `Transfer-Encoding: chunked`,
+/// so it doesn't need to be buffered before the response is written.
+pub struct ChunkedBody {
+    chunks: Box<dyn Iterator<Item = Vec<u8>> + Send>,
+    // HTTP/1.0 clients don't understand chunked framing, the body is sent as is and its end
+    // is signaled by closing the connection
+    framed: bool,
+}
+
+impl Debug for ChunkedBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "ChunkedBody")
+    }
 }
 
-impl Default for ResponseEntityBuilder {
+/// Body of a builder, serialized only when the response is built.
+type PendingBody = Box<dyn FnOnce() -> serde_json::Result<String> + Send>;
+
+/// State of a `ResponseEntityBuilder` whose status wasn't set yet, it can't be built.
+pub struct NoStatus;
+
+/// Builds a `ResponseEntity`. The status is mandatory, `build` only exists once
+/// `with_status_code` was called:
+///
+/// ```ignore
+/// let response = ResponseEntityBuilder::new()
+///     .with_status_code(StatusCode::Created)
+///     .header("Location", "/users/7")
+///     .with_body(user)
+///     .build()?;
+/// ```
+pub struct ResponseEntityBuilder<S = NoStatus> {
+    body: Option<PendingBody>,
+    // Content-Type of the body, sent unless one is set with `header` or `content_type`
+    body_type: Option<&'static str>,
+    chunks: Option<ChunkedBody>,
+    headers: HashMap<String, String>,
+    status: S
+}
+
+impl Default for ResponseEntityBuilder<NoStatus> {
     fn default() -> Self {
-        Self { body: None, headers: HashMap::new(), status: StatusCode::Ok }
+        Self { body: None, body_type: None, chunks: None, headers: HashMap::new(), status: NoStatus }
     }
 }
 
-// TODO: Make builder with obligatory fields. ref https://dev.to/mindflavor/rust-builder-pattern-with-types-3chf
-impl ResponseEntityBuilder {
+impl ResponseEntityBuilder<NoStatus> {
     pub fn new() -> Self {
         Self::default()
     }
+}
+
+impl<S> ResponseEntityBuilder<S> {
+    pub fn with_status_code(self, status: StatusCode) -> ResponseEntityBuilder<StatusCode> {
+        ResponseEntityBuilder {
+            body: self.body,
+            body_type: self.body_type,
+            chunks: self.chunks,
+            headers: self.headers,
+            status
+        }
+    }
 
+    /// Sets `body` as the JSON body, it's serialized by `build`. It's sent as
+    /// `application/json` unless another content type is set.
     pub fn with_body<T>(self, body: T) -> Self
-        where T: Response
+        where T: Response + Send + 'static
     {
-        let b = serde_json::to_string_pretty(&body).unwrap();
         Self {
-            body: Some(b),
-            headers: self.headers,
-            status: self.status
+            body: Some(Box::new(move || serde_json::to_string_pretty(&body))),
+            body_type: Some("application/json"),
+            chunks: None,
+            ..self
         }
     }
 
-    pub fn with_headers(self, headers: HashMap<String, String>) -> Self {
+    /// Sets a body that is sent as it is, as `text/plain` unless another content type is set.
+    pub(in crate) fn with_text_body(self, body: String) -> Self {
         Self {
-            body: self.body,
-            headers,
-            status: self.status
+            body: Some(Box::new(move || Ok(body))),
+            body_type: Some("text/plain; charset=utf-8"),
+            chunks: None,
+            ..self
         }
     }
 
-    pub fn with_status_code(self, status: StatusCode) -> Self {
+    /// Streams the body with chunked transfer-encoding, every item yielded by `chunks` is
+    /// written to the connection as soon as it's produced. Empty items are skipped.
+    pub fn with_chunked_body<I>(self, chunks: I) -> Self
+        where I: IntoIterator<Item = Vec<u8>>, I::IntoIter: Send + 'static
+    {
         Self {
-            body: self.body,
-            headers: self.headers,
-            status
+            body: None,
+            body_type: None,
+            chunks: Some(ChunkedBody { chunks: Box::new(chunks.into_iter()), framed: true }),
+            ..self
         }
     }
 
-    pub fn build(&self) -> ResponseEntity {
-        ResponseEntity::new(self.clone())
+    /// Adds a header, replacing the value set before for the same name.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.retain(|k, _| !k.eq_ignore_ascii_case(name));
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn content_type(self, content_type: &str) -> Self {
+        self.header("Content-Type", content_type)
+    }
+
+    /// Adds every header of `headers`, same as calling `header` for each one.
+    pub fn with_headers(self, headers: HashMap<String, String>) -> Self {
+        headers.iter().fold(self, |builder, (name, value)| builder.header(name, value))
+    }
+}
+
+impl ResponseEntityBuilder<StatusCode> {
+    /// Serializes the body, failing with an internal error when it can't be.
+    pub fn build(mut self) -> Result<ResponseEntity, ApiError> {
+        if let Some(body_type) = self.body_type {
+            if !has_header(&self.headers, "Content-Type") {
+                self.headers.insert(String::from("Content-Type"), body_type.to_string());
+            }
+        }
+
+        let body = match self.body {
+            Some(body) => Some(body().map_err(|err| ApiError::internal_error(String::from("Failed to serialize response body"), err.to_string()))?),
+            None => None,
+        };
+
+        Ok(ResponseEntity::new(body, self.chunks, self.headers, self.status))
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResponseEntity {
     pub(in crate) body: Option<String>,
+    #[serde(skip)]
+    pub(in crate) chunks: Option<ChunkedBody>,
     pub(in crate) headers: HashMap<String, String>,
-    pub(in crate) status: StatusCode
+    pub(in crate) status: StatusCode,
+    // Answer to a HEAD request, the headers are sent as for a GET but not the body
+    #[serde(skip)]
+    pub(in crate) head_only: bool
 }
 
 impl ResponseEntity {
-    pub(in crate) fn new(builder: ResponseEntityBuilder) -> Self {
-        Self {
-            body: builder.body,
-            headers: builder.headers,
-            status: builder.status
+    pub(in crate) fn new(body: Option<String>, chunks: Option<ChunkedBody>, headers: HashMap<String, String>, status: StatusCode) -> Self {
+        Self { body, chunks, headers, status, head_only: false }
+    }
+
+    pub fn status(&self) -> &StatusCode {
+        &self.status
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    /// Adds a header or replaces the value sent for it, for middlewares changing the response
+    /// of the handler.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.retain(|k, _| !k.eq_ignore_ascii_case(name));
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Keeps the body from being written, the `Content-Length` still describes it.
+    pub(in crate) fn strip_body(&mut self) {
+        self.head_only = true;
+    }
+
+    /// Sets the `Connection` header for the response and returns whether the connection can be
+    /// kept open after it's written.
+    pub(in crate) fn prepare_connection(&mut self, keep_alive: bool, http_1_0: bool) -> bool {
+        let mut keep_alive = keep_alive && !self.headers.iter().any(|(k, v)| {
+            k.eq_ignore_ascii_case("Connection") && v.split(',').any(|o| o.trim().eq_ignore_ascii_case("close"))
+        });
+
+        if let Some(chunks) = &mut self.chunks {
+            if http_1_0 {
+                chunks.framed = false;
+                keep_alive = false;
+            }
         }
+
+        self.headers.retain(|k, _| !k.eq_ignore_ascii_case("Connection"));
+
+        if !keep_alive {
+            self.headers.insert(String::from("Connection"), String::from("close"));
+        } else if http_1_0 {
+            self.headers.insert(String::from("Connection"), String::from("keep-alive"));
+        }
+
+        keep_alive
     }
 
-    pub(in crate) fn write(&self, stream: &mut TcpStream) {
-        if let Err(e) = write!(
-            stream,
-            "HTTP/1.1 {} {}{}\r\n\r\n{}",
-            self.status.status_number(),
-            self.status.reason_phrase(),
-            format_headers(self.headers.clone()),
-            self.body.clone().unwrap_or("".to_string())
-        ) {
+    pub(in crate) fn write<W>(self, stream: &mut W)
+        where W: Write
+    {
+        if let Err(e) = self.write_to(stream) {
             println!("Failed to send response: {}", e);
         }
     }
+
+    fn write_to<W>(mut self, stream: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        match self.chunks.take() {
+            Some(chunks) => {
+                stream.write_all(streamed_head(&self.status, self.headers, chunks.framed).as_bytes())?;
+                stream.flush()?;
+
+                if self.head_only {
+                    return Ok(());
+                }
+
+                for chunk in chunks.chunks.filter(|c| !c.is_empty()) {
+                    match chunks.framed {
+                        true => stream.write_all(&encode_chunk(&chunk))?,
+                        false => stream.write_all(&chunk)?,
+                    }
+                    stream.flush()?;
+                }
+
+                if chunks.framed {
+                    stream.write_all(LAST_CHUNK)?;
+                }
+                stream.flush()
+            },
+            None => {
+                let body = self.body.unwrap_or_default();
+
+                self.headers.retain(|k, _| !k.eq_ignore_ascii_case("Content-Length") && !k.eq_ignore_ascii_case("Transfer-Encoding"));
+                add_default_headers(&mut self.headers);
+
+                // 204 responses can't carry a body, not even an empty one
+                if self.status.status_number() != 204 {
+                    self.headers.insert(String::from("Content-Length"), body.len().to_string());
+                }
+
+                let body = match self.head_only {
+                    true => String::new(),
+                    false => body,
+                };
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {} {}{}\r\n\r\n{}",
+                    self.status.status_number(),
+                    self.status.reason_phrase(),
+                    format_headers(self.headers),
+                    body
+                )?;
+                stream.flush()
+            },
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(in crate) async fn write_async<W>(self, stream: &mut W)
+        where W: AsyncWrite + Unpin
+    {
+        if let Err(e) = self.write_to_async(stream).await {
+            println!("Failed to send response: {}", e);
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn write_to_async<W>(mut self, stream: &mut W) -> io::Result<()>
+        where W: AsyncWrite + Unpin
+    {
+        match self.chunks.take() {
+            Some(chunks) => {
+                stream.write_all(streamed_head(&self.status, self.headers, chunks.framed).as_bytes()).await?;
+                stream.flush().await?;
+
+                if self.head_only {
+                    return Ok(());
+                }
+
+                for chunk in chunks.chunks.filter(|c| !c.is_empty()) {
+                    match chunks.framed {
+                        true => stream.write_all(&encode_chunk(&chunk)).await?,
+                        false => stream.write_all(&chunk).await?,
+                    }
+                    stream.flush().await?;
+                }
+
+                if chunks.framed {
+                    stream.write_all(LAST_CHUNK).await?;
+                }
+                stream.flush().await
+            },
+            None => {
+                let mut buffer: Vec<u8> = vec![];
+                self.write_to(&mut buffer)?;
+
+                stream.write_all(&buffer).await?;
+                stream.flush().await
+            },
+        }
+    }
+}
+
+const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
+
+const SERVER: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+fn has_header(headers: &HashMap<String, String>, name: &str) -> bool {
+    headers.keys().any(|k| k.eq_ignore_ascii_case(name))
+}
+
+/// Adds `Date` and `Server` unless the handler set them.
+fn add_default_headers(headers: &mut HashMap<String, String>) {
+    if !has_header(headers, "Date") {
+        headers.insert(String::from("Date"), http_date(SystemTime::now()));
+    }
+    if !has_header(headers, "Server") {
+        headers.insert(String::from("Server"), String::from(SERVER));
+    }
+}
+
+fn streamed_head(status: &StatusCode, mut headers: HashMap<String, String>, framed: bool) -> String {
+    headers.retain(|k, _| !k.eq_ignore_ascii_case("Content-Length") && !k.eq_ignore_ascii_case("Transfer-Encoding"));
+
+    add_default_headers(&mut headers);
+
+    if framed {
+        headers.insert(String::from("Transfer-Encoding"), String::from("chunked"));
+    }
+
+    format!(
+        "HTTP/1.1 {} {}{}\r\n\r\n",
+        status.status_number(),
+        status.reason_phrase(),
+        format_headers(headers)
+    )
+}
+
+fn encode_chunk(chunk: &[u8]) -> Vec<u8> {
+    let mut encoded = format!("{:x}\r\n", chunk.len()).into_bytes();
+    encoded.extend_from_slice(chunk);
+    encoded.extend_from_slice(b"\r\n");
+    encoded
 }
 
 fn format_headers(hash_map: HashMap<String, String>) -> String {
@@ -99,4 +369,97 @@ fn format_headers(hash_map: HashMap<String, String>) -> String {
     }
 
     header_string
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_chunked_body() {
+        let response = ResponseEntityBuilder::new()
+            .with_status_code(StatusCode::Ok)
+            .with_chunked_body(vec![b"hello".to_vec(), vec![], b" chunked world".to_vec()])
+            .build()
+            .unwrap();
+
+        let mut output: Vec<u8> = vec![];
+        response.write(&mut output);
+
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(output.contains("\r\nTransfer-Encoding: chunked\r\n"));
+        assert!(output.ends_with("\r\n\r\n5\r\nhello\r\ne\r\n chunked world\r\n0\r\n\r\n"));
+    }
+
+    #[derive(serde_derive::Serialize)]
+    struct Pairs {
+        pairs: HashMap<(u8, u8), u8>
+    }
+
+    impl Response for Pairs {
+        fn to_string_json(&self) -> String {
+            serde_json::to_string(self).unwrap()
+        }
+    }
+
+    #[test]
+    fn adds_headers_one_by_one() {
+        let response = ResponseEntityBuilder::new()
+            .header("X-Request-Id", "1")
+            .with_headers(HashMap::from([(String::from("X-Trace"), String::from("a"))]))
+            .content_type("text/csv")
+            .header("content-type", "text/plain")
+            .with_status_code(StatusCode::Accepted)
+            .build()
+            .unwrap();
+
+        assert_eq!(response.headers.len(), 3);
+        assert_eq!(response.headers.get("content-type"), Some(&String::from("text/plain")));
+        assert_eq!(response.headers.get("X-Request-Id"), Some(&String::from("1")));
+        assert_eq!(response.status.status_number(), 202);
+
+        let response = response.with_header("x-request-id", "2");
+        assert_eq!(response.headers.len(), 3);
+        assert_eq!(response.headers.get("x-request-id"), Some(&String::from("2")));
+    }
+
+    #[test]
+    fn serialization_errors_are_returned() {
+        let err = ResponseEntityBuilder::new()
+            .with_status_code(StatusCode::Ok)
+            .with_body(Pairs { pairs: HashMap::from([((1, 2), 3)]) })
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err.status().status_number(), 500);
+    }
+
+    #[test]
+    fn adds_default_headers() {
+        let response = ResponseEntityBuilder::new()
+            .with_status_code(StatusCode::Ok)
+            .with_body(Pairs { pairs: HashMap::new() })
+            .build()
+            .unwrap();
+        assert_eq!(response.headers.get("Content-Type"), Some(&String::from("application/json")));
+
+        let length = response.body.as_ref().unwrap().len();
+        let mut output: Vec<u8> = vec![];
+        response.with_header("Server", "custom").write(&mut output);
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(&format!("\r\nContent-Length: {length}\r\n")));
+        assert!(output.contains("\r\nServer: custom\r\n"));
+        assert!(output.contains(" GMT\r\n"));
+
+        let response = ResponseEntityBuilder::new()
+            .with_status_code(StatusCode::Ok)
+            .content_type("application/vnd.api+json")
+            .with_body(Pairs { pairs: HashMap::new() })
+            .build()
+            .unwrap();
+        assert_eq!(response.headers.get("Content-Type"), Some(&String::from("application/vnd.api+json")));
+    }
+}
@@ -0,0 +1,177 @@
+use std::{sync::Arc, fmt::{Formatter, Display, Result as FmtResult}};
+
+use crate::{handler::{self, Handler}, middleware::{self, Middleware}, model::enums::method::Method, server::RouteHandler};
+#[cfg(feature = "async")]
+use crate::handler::AsyncHandler;
+
+/// Group of routes built apart from the `Server`, usually one per module, and mounted under
+/// a prefix with `Server::nest` or nested into another `Router`.
+///
+/// ```ignore
+/// fn users_router() -> Router {
+///     let mut router = Router::new().with_middleware(require_auth);
+///     router.mount(get_route!["/users/{id}", find_user]);
+///     router.mount(post_route!["/users", create_user]);
+///     router
+/// }
+///
+/// server.nest("/api/v1", users_router());
+/// ```
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<(Method, String, RouteHandler)>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `middleware` around every route of the group, nested routers included. Middlewares
+    /// run in the order they were added, outer groups before inner ones.
+    pub fn with_middleware<M>(mut self, middleware: M) -> Self
+        where M: Middleware
+    {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Same as `Server::mount`, with `path` relative to where the router is nested.
+    pub fn mount<H, Args>(&mut self, (method, path, func): (Method, String, H))
+        where H: Handler<Args>
+    {
+        self.routes.push((method, path, RouteHandler::Sync(handler::boxed(func))));
+    }
+
+    #[cfg(feature = "async")]
+    pub fn mount_async<H, Args>(&mut self, (method, path, func): (Method, String, H))
+        where H: AsyncHandler<Args>
+    {
+        self.routes.push((method, path, RouteHandler::Async(handler::boxed_async(func))));
+    }
+
+    /// Mounts every route of `router` under `prefix`.
+    pub fn nest(&mut self, prefix: &str, router: Router) {
+        self.routes.extend(router.into_routes(prefix));
+    }
+
+    /// Routes with their full path and the group middlewares applied.
+    pub(in crate) fn into_routes(self, prefix: &str) -> Vec<(Method, String, RouteHandler)> {
+        let middlewares = self.middlewares;
+
+        self.routes
+            .into_iter()
+            .map(|(method, path, handler)| (method, join_path(prefix, &path), middleware::wrap(handler, &middlewares)))
+            .collect()
+    }
+}
+
+/// Why a route couldn't be mounted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    /// `path` matches the same requests as `existing`, mounted before for the same method.
+    Conflict { method: Method, path: String, existing: String },
+    /// `path` isn't a valid route pattern, like a catch-all that isn't the last segment.
+    InvalidPath { path: String, reason: String },
+}
+
+impl Display for RouteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Conflict { method, path, existing } => write!(f, "{method} {path} conflicts with {method} {existing}, mounted before"),
+            Self::InvalidPath { path, reason } => write!(f, "Invalid route {path}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    let path = path.trim_start_matches('/');
+
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, _) => format!("/{path}"),
+        (false, true) => prefix.to_string(),
+        (false, false) => format!("{prefix}/{path}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::{extract::{RequestContext, StateMap}, middleware::Next, model::{request::RequestObj, header_map::HeaderMap, response_entity::ResponseEntity, into_response::IntoResponse, enums::status_code::StatusCode}};
+
+    fn ok() -> ResponseEntity {
+        StatusCode::Ok.into_response()
+    }
+
+    fn call(handler: &RouteHandler, path: &str) -> ResponseEntity {
+        let request = RequestObj::new(String::from(path), Method::GET, String::from("HTTP/1.1"), HeaderMap::new(), vec![]);
+        let context = RequestContext::new(request, vec![], Arc::new(StateMap::new()));
+
+        match handler {
+            RouteHandler::Sync(f) => f(context),
+            #[cfg(feature = "async")]
+            RouteHandler::Async(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn joins_prefixes() {
+        assert_eq!(join_path("/api/v1", "/users"), "/api/v1/users");
+        assert_eq!(join_path("/api/v1/", "users/{id}"), "/api/v1/users/{id}");
+        assert_eq!(join_path("/api", "/"), "/api");
+        assert_eq!(join_path("", "/users"), "/users");
+        assert_eq!(join_path("/", "/"), "/");
+    }
+
+    #[test]
+    fn nests_routes_under_prefixes() {
+        let mut users = Router::new();
+        users.mount((Method::GET, String::from("/users/{id}"), ok));
+
+        let mut api = Router::new();
+        api.nest("/v1", users);
+        api.mount((Method::GET, String::from("/"), ok));
+
+        let paths: Vec<String> = api.into_routes("/api").into_iter().map(|(_, path, _)| path).collect();
+        assert_eq!(paths, vec![String::from("/api/v1/users/{id}"), String::from("/api")]);
+    }
+
+    #[test]
+    fn runs_group_middlewares_in_order() {
+        let calls = Arc::new(Mutex::new(vec![]));
+
+        let record = |name: &'static str| {
+            let calls = Arc::clone(&calls);
+            move |context: RequestContext, next: Next| {
+                calls.lock().unwrap().push(name);
+                next.run(context)
+            }
+        };
+
+        let mut inner = Router::new().with_middleware(record("inner"));
+        inner.mount((Method::GET, String::from("/"), ok));
+
+        let mut outer = Router::new().with_middleware(record("outer-1")).with_middleware(record("outer-2"));
+        outer.nest("/inner", inner);
+        outer.mount((Method::GET, String::from("/blocked"), ok));
+
+        let mut outer = outer.with_middleware(|context: RequestContext, next: Next| match context.request().path.as_str() {
+            "/blocked" => StatusCode::Forbidden.into_response(),
+            _ => next.run(context),
+        });
+        outer.mount((Method::GET, String::from("/other"), ok));
+
+        let routes = outer.into_routes("");
+
+        assert_eq!(call(&routes[0].2, "/inner").status.status_number(), 200);
+        assert_eq!(*calls.lock().unwrap(), vec!["outer-1", "outer-2", "inner"]);
+
+        assert_eq!(call(&routes[1].2, "/blocked").status.status_number(), 403);
+        assert_eq!(call(&routes[2].2, "/other").status.status_number(), 200);
+    }
+}
@@ -1,131 +1,693 @@
-use std::{net::TcpListener, io::Read, time::Instant, collections::HashMap};
-use serde::{Deserialize, Serialize};
+use std::{net::{TcpListener, TcpStream}, time::Instant, sync::Arc, io::ErrorKind, thread, panic::{self, AssertUnwindSafe}};
 
-use crate::{model::{request::*, response::*, enums::{status_code::*, parse_error::ParseError, method::Method}, Request, response_entity::ResponseEntity}, server_utils::{server_utils::{process_buffer, buffer_to_request}, default_returns::{DefaultReturns, ReturnBody}}, };
+use std::any::{Any, TypeId};
 
-pub trait Handler {
-    fn handle_request<T>(&mut self, request: &RequestObj<T>) -> ResponseObj<T> where T: Serialize + Deserialize<'static>;
+use crate::{config::ServerConfig, router::{Router, RouteError}, middleware::{self, Middleware}, shutdown::{ShutdownHandle, POLL_INTERVAL}, handler::{self, Handler, BoxedHandler}, extract::{RequestContext, StateMap}, model::{request::*, enums::method::Method, response_entity::ResponseEntity}, server_utils::{request_parser::read_request, default_returns::DefaultReturns, thread_pool::{ThreadPool, Backlog}, route_tree::RouteTree, server_utils::Routed}, };
+#[cfg(feature = "async")]
+use crate::{handler::{AsyncHandler, BoxedAsyncHandler}, server_utils::async_server::block_on};
 
-    fn handle_bad_request<T>(&mut self, err: &ParseError) -> ResponseObj<T> where T: Serialize + Deserialize<'static> {
-        println!("Failed to parse request: {}", err);
-        ResponseObj::new(StatusCode::BadRequest, None)
-    }
+pub(in crate) enum RouteHandler {
+    Sync(BoxedHandler),
+    #[cfg(feature = "async")]
+    Async(BoxedAsyncHandler),
+}
+
+pub struct Server { 
+    pub(in crate) addr: String,
+    pub(in crate) config: ServerConfig,
+    pub(in crate) shutdown: ShutdownHandle,
+    pub(in crate) tree: RouteTree<RouteHandler>,
+    pub(in crate) state: StateMap,
+    pub(in crate) middlewares: Vec<Arc<dyn Middleware>>
 }
 
-#[derive(Hash, PartialEq, Eq, Debug, Clone)]
-pub(in crate) struct Endpoint {
-    pub(in crate) method: Method,
-    pub(in crate) path: Vec<String>
+/// Routes mounted on a `Server`, shared between the worker threads once it starts running.
+pub(in crate) struct RouteTable {
+    pub(in crate) tree: RouteTree<RouteHandler>,
+    pub(in crate) state: Arc<StateMap>,
+    pub(in crate) middlewares: Vec<Arc<dyn Middleware>>
+}
+
+impl RouteTable {
+    pub(in crate) fn new(tree: RouteTree<RouteHandler>, state: StateMap, middlewares: Vec<Arc<dyn Middleware>>) -> Self {
+        Self { tree, state: Arc::new(state), middlewares }
+    }
 }
 
-impl Endpoint {
-    fn new(method: Method, path: String) -> Self {
-        let mut path_vec: Vec<String> = path.split("/").map(|p| p.to_string()).collect();
+impl Server {
+    pub fn new(addr: String) -> Self {
+        Self { addr, config: ServerConfig::from_env(), shutdown: ShutdownHandle::new(), tree: RouteTree::new(), state: StateMap::new(), middlewares: vec![] }
+    }
+
+    pub fn with_config(self, config: ServerConfig) -> Self {
+        Self { config, ..self }
+    }
+
+    /// Sets the amount of threads handling connections, same as `ServerConfig::with_workers`.
+    pub fn with_workers(self, workers: usize) -> Self {
+        let config = self.config.clone().with_workers(workers);
+        Self { config, ..self }
+    }
+
+    /// Returns a handle that stops the server once it's running, see `ShutdownHandle`.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Shuts the server down gracefully when the process receives SIGTERM or SIGINT.
+    pub fn with_signal_handling(self) -> Self {
+        self.shutdown.register_signals();
+        self
+    }
 
-        if path_vec.last().unwrap() == "" && path_vec.len() > 1 {
-            path_vec.remove(path_vec.len() - 1);
+    /// Shares `state` with every handler, it's received through the `State<T>` extractor.
+    /// Registering a value of the same type again replaces it.
+    pub fn with_state<T>(mut self, state: T) -> Self
+        where T: Send + Sync + 'static
+    {
+        self.state.insert(TypeId::of::<T>(), Box::new(state) as Box<dyn Any + Send + Sync>);
+        self
+    }
+
+    /// Runs `middleware` around every request, before it's routed. Requests without a route
+    /// and automatic `OPTIONS` answers go through it too. Middlewares run in the order they
+    /// were added, before the ones of groups and routes.
+    pub fn with_middleware<M>(mut self, middleware: M) -> Self
+        where M: Middleware
+    {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Mounts a handler, each route can take its own request body type or none at all,
+    /// see `Handler` for the accepted signatures.
+    ///
+    /// Panics when the route conflicts with one mounted before or the path is malformed, use
+    /// `try_mount` to handle the error instead.
+    pub fn mount<H, Args>(&mut self, route: (Method, String, H))
+        where H: Handler<Args>
+    {
+        if let Err(err) = self.try_mount(route) {
+            panic!("{err}");
         }
+    }
 
-        Self { method, path: path_vec }
+    /// Same as `mount`, returning the error instead of panicking.
+    pub fn try_mount<H, Args>(&mut self, (method, path, func): (Method, String, H)) -> Result<(), RouteError>
+        where H: Handler<Args>
+    {
+        self.tree.insert(method, &path, RouteHandler::Sync(handler::boxed(func)))
     }
-}
 
-pub struct Server<Req> 
-    where Req: Request
-{ 
-    pub(in crate) addr: String,
-    pub(in crate) funcs: HashMap<Endpoint, fn(HashMap<String, String>, HashMap<String, String>, Req) -> ResponseEntity>
-}
+    /// Mounts an `async fn` handler, it's awaited on the tokio runtime when the server runs
+    /// with `serve` and driven to completion on the worker thread when it runs with `run`.
+    ///
+    /// Panics like `mount`.
+    #[cfg(feature = "async")]
+    pub fn mount_async<H, Args>(&mut self, route: (Method, String, H))
+        where H: AsyncHandler<Args>
+    {
+        if let Err(err) = self.try_mount_async(route) {
+            panic!("{err}");
+        }
+    }
 
-// TODO: add middleware support. Maybe have three macros, one for only a endpoint function, other for middleware and endpoint, and other for global middleware
-impl<'s, Req> Server<Req> 
-    where Req: Request
-{
-    pub fn new(addr: String) -> Self {
-        Self { addr, funcs: HashMap::new() }
+    #[cfg(feature = "async")]
+    pub fn try_mount_async<H, Args>(&mut self, (method, path, func): (Method, String, H)) -> Result<(), RouteError>
+        where H: AsyncHandler<Args>
+    {
+        self.tree.insert(method, &path, RouteHandler::Async(handler::boxed_async(func)))
     }
 
-    pub fn mount(&mut self, (method, path, func): (Method, String, fn(HashMap<String, String>, HashMap<String, String>, Req) -> ResponseEntity)) {
-        self.funcs.insert(Endpoint::new(method, path), func);
+    /// Mounts every route of `router` under `prefix`, with the router's middlewares applied.
+    ///
+    /// Panics like `mount`.
+    pub fn nest(&mut self, prefix: &str, router: Router) {
+        if let Err(err) = self.try_nest(prefix, router) {
+            panic!("{err}");
+        }
     }
 
-    pub fn run(self) {
-        println!("Listening to {}", self.addr);
+    /// Same as `nest`, returning the first error. The routes before the failing one stay mounted.
+    pub fn try_nest(&mut self, prefix: &str, router: Router) -> Result<(), RouteError> {
+        router
+            .into_routes(prefix)
+            .into_iter()
+            .try_for_each(|(method, path, handler)| self.tree.insert(method, &path, handler))
+    }
 
+    /// Accepts connections until a shutdown is requested through a `ShutdownHandle`, then waits
+    /// for the requests being handled and returns.
+    pub fn run(self) {
         // TODO: TcpListener for each endpoint?
         let listener = TcpListener::bind(&self.addr).unwrap();
 
-        loop {
+        self.run_on(listener);
+    }
+
+    /// Same as `run` on an already bound listener instead of the server's address, like one
+    /// bound to port 0 to get a free port.
+    pub fn run_on(self, listener: TcpListener) {
+        match listener.local_addr() {
+            Ok(addr) => println!("Listening to {} with {} workers", addr, self.config.workers),
+            Err(_) => println!("Listening to {} with {} workers", self.addr, self.config.workers),
+        }
+
+        // Non blocking so the loop can notice a shutdown while no connection arrives
+        listener.set_nonblocking(true).unwrap();
+
+        let routes = Arc::new(RouteTable::new(self.tree, self.state, self.middlewares));
+        let config = Arc::new(self.config);
+        let pool = ThreadPool::new(config.workers, config.queue_size);
+        let backlog = pool.backlog();
+
+        while !self.shutdown.is_shutdown() {
             match listener.accept() {
-                Ok((mut stream, _)) => {
-                    // TODO: Change byte size based on dotenv
-                    let mut buffer: Vec<u8> = [0_u8; 10240].to_vec();
-                    match stream.read(&mut buffer) {
-                        Ok(_) => {
-                            println!("Request received!");
-                            let now = Instant::now();
-
-                            let processed_buf = process_buffer(&buffer);
-
-                            let request_obj = buffer_to_request(&processed_buf);
-                            let path = request_obj.path.clone();
-                            let method = request_obj.method.clone();
-
-                            println!("Calling function for method {} and path {}", method, path);
-
-                            let (func_key, params) = self.parse_path_return_func(Endpoint::new(method.clone(), path.clone()));
-
-                            if let Some(k) = func_key {
-                                let func = self.funcs.get(&k);
-                                
-                                match func {
-                                    Some(f) => {
-                                        println!("Function found");
-    
-                                        let body = request_obj.body.clone();
-                                        let return_obj = f(request_obj.headers, params, Request::string_body_to_obj(body.to_owned()));
-    
-                                        println!("Received ResponseEntity, returning");
-    
-                                        return_obj.write(&mut stream);
-    
-                                        println!("Elapsed time: {:?}", now.elapsed());
-                                        continue;
-                                    },
-                                    None => {
-                                        DefaultReturns::func_not_found(&mut stream, method, path);
-    
-                                        println!("Elapsed time: {:?}", now.elapsed());
-                                        continue;
-                                    },
-                                }
-                            } else {
-                                DefaultReturns::func_not_found(&mut stream, method, path);
-    
-                                println!("Elapsed time: {:?}", now.elapsed());
-                                continue;
-                            }
-                        },
-                        Err(err) => {
-                            println!("Failed to read from connection: {}", err);
-
-                            DefaultReturns::internal_error(
-                                &mut stream, 
-                                Some(ReturnBody::new(
-                                    None,
-                                    String::from("A internal error ocurred while reading the request"),
-                                    String::from(format!("{}", err))
-                                ))
-                            );
-                        },
+                Ok((stream, _)) => {
+                    if let Err(err) = stream.set_nonblocking(false) {
+                        println!("Failed to configure connection: {err}");
+                        continue;
                     }
+
+                    let routes = Arc::clone(&routes);
+                    let config = Arc::clone(&config);
+                    let shutdown = self.shutdown.clone();
+                    let backlog = backlog.clone();
+
+                    pool.execute(move || handle_connection(stream, &routes, &config, &shutdown, &backlog));
                 },
+                Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
                 Err(err) => {
                     println!("Failed to stablish connection: {err}");
                     continue;
                 },
             }
         }
+
+        println!("Shutting down, waiting for requests in flight");
+        drop(listener);
+
+        if !self.shutdown.wait_drained(config.shutdown_timeout) {
+            println!("Shutdown timeout reached with requests still in flight");
+        }
+
+        // Idle keep-alive connections may still hold workers, they finish on their own
+        pool.detach();
+    }
+
+}
+
+fn handle_connection(mut stream: TcpStream, routes: &Arc<RouteTable>, config: &ServerConfig, shutdown: &ShutdownHandle, backlog: &Backlog) {
+    let mut buffer: Vec<u8> = vec![];
+    let mut served = 0;
+
+    loop {
+        if served > 0 && buffer.is_empty() && !wait_for_request(&stream, config, backlog) {
+            return;
+        }
+
+        let request_obj = match read_request(&mut stream, &mut buffer, config) {
+            Ok(request_obj) => request_obj,
+            Err(err) => {
+                if let Some(mut response) = DefaultReturns::read_failure(err) {
+                    response.prepare_connection(false, false);
+                    response.write(&mut stream);
+                }
+
+                return;
+            },
+        };
+
+        let _guard = match shutdown.start_request() {
+            Some(guard) => guard,
+            None => {
+                let mut response = DefaultReturns::shutting_down();
+                response.prepare_connection(false, false);
+                response.write(&mut stream);
+
+                return;
+            },
+        };
+
+        println!("Request received!");
+        let now = Instant::now();
+
+        served += 1;
+        let keep_alive = request_obj.keep_alive() && served < config.max_requests_per_connection;
+        let http_1_0 = request_obj.is_http_1_0();
+
+        let mut return_obj = respond(routes, request_obj);
+        let keep_alive = return_obj.prepare_connection(keep_alive && !shutdown.is_shutdown(), http_1_0);
+
+        println!("Received ResponseEntity, returning");
+
+        return_obj.write(&mut stream);
+
+        println!("Elapsed time: {:?}", now.elapsed());
+
+        if !keep_alive {
+            return;
+        }
+    }
+}
+
+/// Waits for the next request on a kept alive connection, returns `false` when the connection
+/// should be closed instead: the client closed it, it stayed idle for the keep-alive timeout or
+/// other connections are waiting for the worker it holds.
+fn wait_for_request(stream: &TcpStream, config: &ServerConfig, backlog: &Backlog) -> bool {
+    let deadline = Instant::now() + config.keep_alive_timeout;
+    let mut byte = [0_u8; 1];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() || !backlog.is_empty() {
+            return false;
+        }
+
+        if let Err(err) = stream.set_read_timeout(Some(remaining.min(POLL_INTERVAL))) {
+            println!("Failed to set the connection timeout: {}", err);
+            return false;
+        }
+
+        match stream.peek(&mut byte) {
+            Ok(size) => return size > 0,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(_) => return false,
+        }
+    }
+}
+
+fn respond(routes: &Arc<RouteTable>, request_obj: RequestObj<Vec<u8>>) -> ResponseEntity {
+    let method = request_obj.method.clone();
+
+    println!("Calling function for method {} and path {}", method, request_obj.path);
+
+    let context = RequestContext::new(request_obj, vec![], Arc::clone(&routes.state));
+
+    // A panicking handler still gets the client an answer
+    let mut response = match panic::catch_unwind(AssertUnwindSafe(|| run_middlewares(routes, context))) {
+        Ok(response) => response,
+        Err(_) => DefaultReturns::handler_panicked(),
+    };
+
+    if method == Method::HEAD {
+        response.strip_body();
+    }
+
+    response
+}
+
+/// Runs the global middlewares with the routing as their innermost step.
+pub(in crate) fn run_middlewares(routes: &Arc<RouteTable>, context: RequestContext) -> ResponseEntity {
+    if routes.middlewares.is_empty() {
+        return dispatch(routes, context);
+    }
+
+    let table = Arc::clone(routes);
+    middleware::chain(&routes.middlewares, Arc::new(move |context| dispatch(&table, context)))(context)
+}
+
+fn dispatch(routes: &RouteTable, context: RequestContext) -> ResponseEntity {
+    let method = context.request.method.clone();
+    let path = context.request.path.clone();
+
+    match routes.route(&method, &path) {
+        Routed::Handler(f, params) => {
+            println!("Function found");
+
+            let context = context.with_path_params(params);
+
+            match f {
+                RouteHandler::Sync(f) => f(context),
+                #[cfg(feature = "async")]
+                RouteHandler::Async(f) => block_on(f(context)),
+            }
+        },
+        Routed::Options(allowed) => DefaultReturns::options(&allowed),
+        Routed::MethodNotAllowed(allowed) => DefaultReturns::method_not_allowed(method, path, &allowed),
+        Routed::NotFound => DefaultReturns::func_not_found(method, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::{Read, Write}, time::Duration, collections::HashMap, sync::{Mutex, mpsc}};
+    use crate::{extract::{Path, Query, Json, State}, model::{Request, Response, query_map::QueryMap, header_map::HeaderMap, response_entity::ResponseEntityBuilder, api_error::ApiError, enums::status_code::StatusCode}};
+    use serde_derive::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Clone)]
+    struct Empty {}
+
+    impl Request for Empty {
+        fn string_body_to_obj(_body: String) -> Result<Self, ApiError> {
+            Ok(Empty {})
+        }
+    }
+
+    fn slow(_headers: HeaderMap, _params: HashMap<String, String>, _req: Empty) -> StatusCode {
+        thread::sleep(Duration::from_millis(300));
+        StatusCode::Ok
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    struct Named {
+        name: String
+    }
+
+    impl Request for Named {
+        fn string_body_to_obj(body: String) -> Result<Self, ApiError> {
+            Ok(serde_json::from_str(&body)?)
+        }
     }
 
+    #[derive(Serialize, Clone)]
+    struct NamedResponse {
+        name: String
+    }
+
+    impl Response for NamedResponse {
+        fn to_string_json(&self) -> String {
+            serde_json::to_string(self).unwrap()
+        }
+    }
+
+    fn greet(_headers: HeaderMap, _params: HashMap<String, String>, req: Named) -> Result<ResponseEntity, ApiError> {
+        ResponseEntityBuilder::new().with_status_code(StatusCode::Ok).header("X-Name", &req.name).build()
+    }
+
+    fn health(_headers: HeaderMap, _params: HashMap<String, String>) -> StatusCode {
+        StatusCode::Ok
+    }
+
+    fn find(query: QueryMap) -> Result<ResponseEntity, ApiError> {
+        match query.get("id") {
+            Some(_) => ResponseEntityBuilder::new().with_status_code(StatusCode::Ok).build(),
+            None => Err(ApiError::not_found(String::from("Missing id"), String::from("No id was sent"))),
+        }
+    }
+
+    fn request(method: Method, path: &str, body: &str) -> RequestObj<Vec<u8>> {
+        RequestObj::new(path.to_string(), method, String::from("HTTP/1.1"), HeaderMap::new(), body.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn routes_take_their_own_body_types() {
+        let mut server = Server::new(String::from("127.0.0.1:0"));
+        server.mount((Method::POST, String::from("/greet"), greet));
+        server.mount((Method::POST, String::from("/slow"), slow));
+        server.mount((Method::GET, String::from("/health"), health));
+
+        let routes = Arc::new(RouteTable::new(server.tree, server.state, server.middlewares));
+
+        let response = respond(&routes, request(Method::POST, "/greet", r#"{"name":"ferris"}"#));
+        assert_eq!(response.headers.get("X-Name"), Some(&String::from("ferris")));
+
+        let response = respond(&routes, request(Method::GET, "/health", ""));
+        assert_eq!(response.status.status_number(), 200);
+
+        let response = respond(&routes, request(Method::GET, "/health?verbose=true#status", ""));
+        assert_eq!(response.status.status_number(), 200);
+    }
+
+    #[test]
+    fn handler_errors_become_responses() {
+        let mut server = Server::new(String::from("127.0.0.1:0"));
+        server.mount((Method::POST, String::from("/greet"), greet));
+        server.mount((Method::GET, String::from("/find"), find));
+        server.mount((Method::GET, String::from("/panic"), || -> StatusCode { panic!("handler failed") }));
+
+        let routes = Arc::new(RouteTable::new(server.tree, server.state, server.middlewares));
+
+        let response = respond(&routes, request(Method::POST, "/greet", "{ \"name\": "));
+        assert_eq!(response.status.status_number(), 400);
+
+        let response = respond(&routes, request(Method::GET, "/find", ""));
+        assert_eq!(response.status.status_number(), 404);
+        assert_eq!(response.headers.get("Content-Type"), Some(&String::from("application/problem+json")));
+        assert!(response.body.unwrap().contains("\"title\": \"Missing id\""));
+
+        let response = respond(&routes, request(Method::GET, "/panic", ""));
+        assert_eq!(response.status.status_number(), 500);
+
+        let response = respond(&routes, request(Method::GET, "/find?id=3", ""));
+        assert_eq!(response.status.status_number(), 200);
+    }
+
+    #[derive(Deserialize)]
+    struct Page {
+        size: usize
+    }
+
+    fn greet_many(Path((id,)): Path<(u64,)>, Query(page): Query<Page>, Json(named): Json<Named>, State(greeting): State<&'static str>) -> Result<ResponseEntity, ApiError> {
+        ResponseEntityBuilder::new()
+            .with_status_code(StatusCode::Ok)
+            .header("X-Greeting", &format!("{greeting} {} #{id} x{}", named.name, page.size))
+            .build()
+    }
+
+    #[test]
+    fn handlers_take_extractors() {
+        let mut server = Server::new(String::from("127.0.0.1:0")).with_state("Hello");
+        server.mount((Method::POST, String::from("/greet/{id}"), greet_many));
+
+        let routes = Arc::new(RouteTable::new(server.tree, server.state, server.middlewares));
+
+        let response = respond(&routes, request(Method::POST, "/greet/7?size=2", r#"{"name":"ferris"}"#));
+        assert_eq!(response.headers.get("X-Greeting"), Some(&String::from("Hello ferris #7 x2")));
+
+        let response = respond(&routes, request(Method::POST, "/greet/seven?size=2", r#"{"name":"ferris"}"#));
+        assert_eq!(response.status.status_number(), 400);
+    }
+
+    #[test]
+    fn handlers_return_plain_values() {
+        let mut server = Server::new(String::from("127.0.0.1:0"));
+        server.mount((Method::GET, String::from("/ping"), || "pong"));
+        server.mount((Method::GET, String::from("/named/{name}"), |Path(name): Path<String>| match name.as_str() {
+            "ferris" => Some((StatusCode::Created, NamedResponse { name })),
+            _ => None,
+        }));
+
+        let routes = Arc::new(RouteTable::new(server.tree, server.state, server.middlewares));
+
+        let response = respond(&routes, request(Method::GET, "/ping", ""));
+        assert_eq!(response.body.as_deref(), Some("pong"));
+
+        let response = respond(&routes, request(Method::GET, "/named/ferris", ""));
+        assert_eq!(response.status.status_number(), 201);
+
+        let response = respond(&routes, request(Method::GET, "/named/other", ""));
+        assert_eq!(response.status.status_number(), 404);
+    }
+
+    #[test]
+    fn answers_other_methods_on_mounted_paths() {
+        let mut server = Server::new(String::from("127.0.0.1:0"));
+        server.mount((Method::GET, String::from("/health"), health));
+        server.mount((Method::POST, String::from("/greet"), greet));
+
+        let routes = Arc::new(RouteTable::new(server.tree, server.state, server.middlewares));
+
+        let response = respond(&routes, request(Method::DELETE, "/health", ""));
+        assert_eq!(response.status.status_number(), 405);
+        assert_eq!(response.headers.get("Allow"), Some(&String::from("GET, HEAD, OPTIONS")));
+
+        let response = respond(&routes, request(Method::OPTIONS, "/greet", ""));
+        assert_eq!(response.status.status_number(), 204);
+        assert_eq!(response.headers.get("Allow"), Some(&String::from("POST, OPTIONS")));
+
+        let response = respond(&routes, request(Method::OPTIONS, "/missing", ""));
+        assert_eq!(response.status.status_number(), 404);
+    }
+
+    #[test]
+    fn head_requests_use_get_handlers_without_body() {
+        let mut server = Server::new(String::from("127.0.0.1:0"));
+        server.mount((Method::GET, String::from("/named"), |_: HeaderMap, _: HashMap<String, String>| {
+            ResponseEntityBuilder::new().with_status_code(StatusCode::Ok).with_body(NamedResponse { name: String::from("ferris") }).build()
+        }));
+
+        let routes = Arc::new(RouteTable::new(server.tree, server.state, server.middlewares));
+
+        let length = respond(&routes, request(Method::GET, "/named", "")).body.unwrap().len();
+
+        let mut output: Vec<u8> = vec![];
+        respond(&routes, request(Method::HEAD, "/named", "")).write(&mut output);
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("HTTP/1.1 200 OK"));
+        assert!(output.contains(&format!("Content-Length: {length}")));
+        assert!(output.ends_with("\r\n\r\n"));
+    }
+
+    fn require_name(context: RequestContext, next: middleware::Next) -> ResponseEntity {
+        match context.request().headers.get("X-Name") {
+            Some(_) => next.run(context),
+            None => ApiError::new(StatusCode::Unauthorized, String::from("Missing name"), String::from("No X-Name header was sent")).into(),
+        }
+    }
+
+    #[test]
+    fn middlewares_wrap_routes_and_requests() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let recorded = Arc::clone(&calls);
+
+        let mut server = Server::new(String::from("127.0.0.1:0")).with_middleware(move |context: RequestContext, next: middleware::Next| {
+            recorded.lock().unwrap().push(context.request().path.clone());
+
+            next.run(context).with_header("X-Global", "true")
+        });
+        server.mount((Method::GET, String::from("/health"), health));
+        server.mount((Method::GET, String::from("/private/{id}"), middleware::layer(greet_private, vec![Arc::new(require_name)])));
+
+        let routes = Arc::new(RouteTable::new(server.tree, server.state, server.middlewares));
+
+        let response = respond(&routes, request(Method::GET, "/private/7", ""));
+        assert_eq!(response.status.status_number(), 401);
+        assert_eq!(response.headers.get("X-Global"), Some(&String::from("true")));
+
+        let mut named = request(Method::GET, "/private/7", "");
+        named.headers.insert("X-Name", "ferris");
+        let response = respond(&routes, named);
+        assert_eq!(response.headers.get("X-Id"), Some(&String::from("7")));
+
+        let response = respond(&routes, request(Method::GET, "/missing", ""));
+        assert_eq!(response.status.status_number(), 404);
+        assert_eq!(response.headers.get("X-Global"), Some(&String::from("true")));
+
+        assert_eq!(*calls.lock().unwrap(), vec!["/private/7", "/private/7", "/missing"]);
+    }
+
+    fn greet_private(Path(id): Path<u64>) -> Result<ResponseEntity, ApiError> {
+        ResponseEntityBuilder::new().with_status_code(StatusCode::Ok).header("X-Id", &id.to_string()).build()
+    }
+
+    #[test]
+    fn rejects_conflicting_routes() {
+        let mut server = Server::new(String::from("127.0.0.1:0"));
+        server.mount((Method::GET, String::from("/users/{id}"), health));
+
+        let err = server.try_mount((Method::GET, String::from("/users/{name}"), health)).unwrap_err();
+        assert_eq!(err.to_string(), "GET /users/{name} conflicts with GET /users/{id}, mounted before");
+
+        let mut router = Router::new();
+        router.mount((Method::GET, String::from("/{id}"), health));
+        assert!(server.try_nest("/users", router).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "GET /health conflicts with GET /health")]
+    fn mounting_a_duplicate_route_panics() {
+        let mut server = Server::new(String::from("127.0.0.1:0"));
+        server.mount((Method::GET, String::from("/health"), health));
+        server.mount((Method::GET, String::from("/health"), health));
+    }
+
+    #[test]
+    fn shutdown_drains_requests_in_flight() {
+        let (started, on_start) = mpsc::channel();
+        let (release, on_release) = mpsc::channel::<()>();
+        let (started, on_release) = (Mutex::new(started), Mutex::new(on_release));
+
+        let mut server = Server::new(String::from("127.0.0.1:0"));
+        server.mount((Method::GET, String::from("/slow"), move || {
+            started.lock().unwrap().send(()).unwrap();
+            on_release.lock().unwrap().recv().unwrap();
+            StatusCode::Ok
+        }));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+        let running = thread::spawn(move || server.run_on(listener));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /slow HTTP/1.1\r\n\r\n").unwrap();
+
+        // The request is in flight once its handler runs
+        on_start.recv().unwrap();
+        handle.shutdown();
+        release.send(()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Connection: close"));
+
+        running.join().unwrap();
+        assert!(TcpStream::connect(addr).is_err());
+    }
+
+    fn read_head(stream: &mut TcpStream) -> String {
+        let mut head = vec![];
+        let mut byte = [0_u8; 1];
+
+        while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() > 0 {
+            head.push(byte[0]);
+        }
+
+        String::from_utf8(head).unwrap()
+    }
+
+    #[test]
+    fn trickling_clients_time_out() {
+        let config = ServerConfig::default().with_request_timeout(Duration::from_millis(200));
+        let mut server = Server::new(String::from("127.0.0.1:0")).with_config(config);
+        server.mount((Method::GET, String::from("/health"), health));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+        let running = thread::spawn(move || server.run_on(listener));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+
+        // One byte every 50ms never leaves a single read waiting long
+        thread::spawn(move || {
+            for byte in b"GET /health HTTP/1.1\r\nX-Padding: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n\r\n" {
+                thread::sleep(Duration::from_millis(50));
+
+                if writer.write_all(&[*byte]).is_err() {
+                    return;
+                }
+            }
+        });
+
+        assert!(read_head(&mut stream).starts_with("HTTP/1.1 408 Request Timeout"));
+
+        handle.shutdown();
+        running.join().unwrap();
+    }
+
+    #[test]
+    fn idle_connections_give_up_busy_workers() {
+        let config = ServerConfig::default().with_workers(1).with_queue_size(1).with_keep_alive_timeout(Duration::from_secs(30));
+        let mut server = Server::new(String::from("127.0.0.1:0")).with_config(config);
+        server.mount((Method::GET, String::from("/health"), health));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+        let running = thread::spawn(move || server.run_on(listener));
+
+        // Kept alive and idle, holding the only worker
+        let mut idle = TcpStream::connect(addr).unwrap();
+        idle.write_all(b"GET /health HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_head(&mut idle).starts_with("HTTP/1.1 200 OK"));
+
+        let started = Instant::now();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /health HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        assert!(read_head(&mut stream).starts_with("HTTP/1.1 200 OK"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(idle.read(&mut [0_u8; 1]).unwrap(), 0);
+
+        handle.shutdown();
+        running.join().unwrap();
+    }
 }
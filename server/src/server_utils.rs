@@ -1,2 +1,9 @@
 pub(in crate) mod server_utils;
-pub(in crate) mod default_returns;
\ No newline at end of file
+pub(in crate) mod default_returns;
+pub(in crate) mod request_parser;
+pub(in crate) mod route_tree;
+pub(in crate) mod params;
+pub(in crate) mod thread_pool;
+pub(in crate) mod http_date;
+#[cfg(feature = "async")]
+pub(in crate) mod async_server;
\ No newline at end of file
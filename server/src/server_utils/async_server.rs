@@ -0,0 +1,204 @@
+use std::{future::Future, sync::Arc, time::Instant, io};
+
+use tokio::{net::{TcpListener, TcpStream}, runtime::{Builder, Runtime, Handle}};
+
+use crate::{config::ServerConfig, extract::RequestContext, shutdown::ShutdownHandle, model::{request::RequestObj, response_entity::ResponseEntity, enums::method::Method}, server::{Server, RouteTable, RouteHandler, run_middlewares}, server_utils::{request_parser::read_request_async, default_returns::DefaultReturns, server_utils::Routed}};
+
+thread_local! {
+    static RUNTIME: Runtime = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the worker runtime");
+}
+
+/// Runs an async handler to completion on the current thread. Worker threads of the blocking
+/// `run` use their own runtime, blocking threads of `serve` (sync handlers wrapping an async
+/// one, like middlewares) use the runtime that spawned them.
+pub(in crate) fn block_on<F>(future: F) -> F::Output
+    where F: Future
+{
+    match Handle::try_current() {
+        Ok(handle) => handle.block_on(future),
+        Err(_) => RUNTIME.with(|runtime| runtime.block_on(future)),
+    }
+}
+
+impl Server {
+    /// Async counterpart of `run`, accepts connections with a `tokio::net::TcpListener` and
+    /// handles each one in its own task. Sync handlers are moved to the blocking thread pool.
+    ///
+    /// Returns once a shutdown is requested and the requests in flight finished.
+    pub async fn serve(self) -> io::Result<()> {
+        let listener = TcpListener::bind(&self.addr).await?;
+
+        println!("Listening to {}", self.addr);
+
+        let routes = Arc::new(RouteTable::new(self.tree, self.state, self.middlewares));
+        let config = Arc::new(self.config);
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        tokio::spawn(handle_connection(stream, Arc::clone(&routes), Arc::clone(&config), self.shutdown.clone()));
+                    },
+                    Err(err) => {
+                        println!("Failed to stablish connection: {err}");
+                        continue;
+                    },
+                },
+                _ = self.shutdown.requested() => break,
+            }
+        }
+
+        println!("Shutting down, waiting for requests in flight");
+        drop(listener);
+
+        if !self.shutdown.wait_drained_async(config.shutdown_timeout).await {
+            println!("Shutdown timeout reached with requests still in flight");
+        }
+
+        Ok(())
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, routes: Arc<RouteTable>, config: Arc<ServerConfig>, shutdown: ShutdownHandle) {
+    let mut buffer: Vec<u8> = vec![];
+    let mut served = 0;
+
+    loop {
+        // Idle connections only cost a task here, they wait for the whole keep-alive timeout
+        if served > 0 && buffer.is_empty() {
+            match tokio::time::timeout(config.keep_alive_timeout, stream.peek(&mut [0_u8; 1])).await {
+                Ok(Ok(size)) if size > 0 => {},
+                _ => return,
+            }
+        }
+
+        let request_obj = match read_request_async(&mut stream, &mut buffer, &config).await {
+            Ok(request_obj) => request_obj,
+            Err(err) => {
+                if let Some(mut response) = DefaultReturns::read_failure(err) {
+                    response.prepare_connection(false, false);
+                    response.write_async(&mut stream).await;
+                }
+
+                return;
+            },
+        };
+
+        let _guard = match shutdown.start_request() {
+            Some(guard) => guard,
+            None => {
+                let mut response = DefaultReturns::shutting_down();
+                response.prepare_connection(false, false);
+                response.write_async(&mut stream).await;
+
+                return;
+            },
+        };
+
+        println!("Request received!");
+        let now = Instant::now();
+
+        served += 1;
+        let keep_alive = request_obj.keep_alive() && served < config.max_requests_per_connection;
+        let http_1_0 = request_obj.is_http_1_0();
+
+        let mut return_obj = respond(&routes, request_obj).await;
+        let keep_alive = return_obj.prepare_connection(keep_alive && !shutdown.is_shutdown(), http_1_0);
+
+        println!("Received ResponseEntity, returning");
+
+        return_obj.write_async(&mut stream).await;
+
+        println!("Elapsed time: {:?}", now.elapsed());
+
+        if !keep_alive {
+            return;
+        }
+    }
+}
+
+async fn respond(routes: &Arc<RouteTable>, request_obj: RequestObj<Vec<u8>>) -> ResponseEntity {
+    let method = request_obj.method.clone();
+
+    println!("Calling function for method {} and path {}", method, request_obj.path);
+
+    let context = RequestContext::new(request_obj, vec![], Arc::clone(&routes.state));
+
+    // Middlewares are sync, so with global ones the whole chain runs on the blocking pool
+    let mut response = match routes.middlewares.is_empty() {
+        true => dispatch(routes, context).await,
+        false => {
+            let routes = Arc::clone(routes);
+            run_blocking(move || run_middlewares(&routes, context)).await
+        },
+    };
+
+    if method == Method::HEAD {
+        response.strip_body();
+    }
+
+    response
+}
+
+async fn dispatch(routes: &RouteTable, context: RequestContext) -> ResponseEntity {
+    let method = context.request.method.clone();
+    let path = context.request.path.clone();
+
+    match routes.route(&method, &path) {
+        Routed::Handler(f, params) => {
+            println!("Function found");
+
+            let context = context.with_path_params(params);
+
+            match f {
+                RouteHandler::Sync(f) => {
+                    let f = Arc::clone(f);
+                    run_blocking(move || f(context)).await
+                },
+                // Spawned so a panicking handler fails its task instead of the connection's
+                RouteHandler::Async(f) => match tokio::spawn(f(context)).await {
+                    Ok(response) => response,
+                    Err(_) => DefaultReturns::handler_panicked(),
+                },
+            }
+        },
+        Routed::Options(allowed) => DefaultReturns::options(&allowed),
+        Routed::MethodNotAllowed(allowed) => DefaultReturns::method_not_allowed(method, path, &allowed),
+        Routed::NotFound => DefaultReturns::func_not_found(method, path),
+    }
+}
+
+async fn run_blocking<F>(f: F) -> ResponseEntity
+    where F: FnOnce() -> ResponseEntity + Send + 'static
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(return_obj) => return_obj,
+        Err(_) => DefaultReturns::handler_panicked(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_on_drives_futures_on_worker_threads() {
+        let handle = std::thread::spawn(|| block_on(async { 40 + 2 }));
+
+        assert_eq!(handle.join().unwrap(), 42);
+    }
+
+    #[test]
+    fn block_on_uses_the_serving_runtime_on_blocking_threads() {
+        let runtime = Builder::new_multi_thread().enable_all().build().unwrap();
+
+        let result = runtime.block_on(async {
+            tokio::task::spawn_blocking(|| block_on(async { tokio::task::yield_now().await; 42 })).await.unwrap()
+        });
+
+        assert_eq!(result, 42);
+    }
+}
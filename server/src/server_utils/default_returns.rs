@@ -1,78 +1,84 @@
-use std::{net::TcpStream, io::Write};
+use std::collections::HashMap;
 
-use serde_derive::{Serialize, Deserialize};
+use crate::{model::{enums::{status_code::StatusCode, method::Method, parse_error::ParseError}, response_entity::ResponseEntity, api_error::ApiError}, server_utils::request_parser::ReadError};
 
-use crate::model::enums::{status_code::StatusCode, method::Method};
+/// Responses the server answers with on its own, errors are sent as `ApiError` problems.
+#[derive(Debug)]
+pub struct DefaultReturns;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ReturnBody {
-    error: Option<String>,
-    message: String,
-    detail: String
-}
+impl DefaultReturns {
+    pub fn internal_error(detail: Option<String>) -> ResponseEntity {
+        let detail = detail.unwrap_or_else(|| String::from("An unknown internal error occurred"));
 
-impl Default for ReturnBody {
-    fn default() -> Self {
-        Self { 
-            error: None, 
-            message: String::from("No message available"), 
-            detail: String::from("A unknown internal error ocurred.")
-        }
+        ApiError::from_status(StatusCode::InternalServerError, detail).into()
     }
-}
 
-impl ReturnBody {
-    pub fn new(error: Option<String>, message: String, detail: String) -> Self {
-        Self { error, message, detail }
+    pub fn parse_failure(err: &ParseError) -> ResponseEntity {
+        println!("Failed to parse request: {err}");
+
+        ApiError::from_status(err.status_code(), format!("The request is not a valid HTTP/1.1 message: {err}")).into()
     }
 
-    pub fn to_string_body(&self) -> String {
-        serde_json::to_string_pretty(self).unwrap()
+    pub fn request_timeout() -> ResponseEntity {
+        let detail = String::from("The connection was idle for too long before the request was complete");
+
+        ApiError::from_status(StatusCode::RequestTimeout, detail).into()
     }
-}
 
-#[derive(Debug)]
-pub struct DefaultReturns;
+    pub fn shutting_down() -> ResponseEntity {
+        let detail = String::from("The server stopped accepting requests, try again on a new connection");
 
-impl DefaultReturns {
-    pub fn not_found(stream: &mut TcpStream, body: ReturnBody) {    
-        if let Err(e) = write!(
-            stream,
-            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\n\r\n{}",
-            StatusCode::NotFound.status_number(),
-            StatusCode::NotFound.reason_phrase(),
-            body.to_string_body()
-        ) {
-            println!("Failed to send response: {}", e);
-        }
+        ApiError::from_status(StatusCode::ServiceUnavailable, detail).into()
     }
 
-    pub fn internal_error(stream: &mut TcpStream, body: Option<ReturnBody>) {
-        let body = body.unwrap_or(ReturnBody::default());
-
-        if let Err(e) = write!(
-            stream,
-            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\n\r\n{}",
-            StatusCode::InternalServerError.status_number(),
-            StatusCode::InternalServerError.reason_phrase(),
-            body.to_string_body()
-        ) {
-            println!("Failed to send response: {}", e);
+    /// Response for a request that couldn't be read, `None` when the client closed the connection.
+    pub(in crate) fn read_failure(err: ReadError) -> Option<ResponseEntity> {
+        match err {
+            ReadError::Closed => None,
+            ReadError::TimedOut => Some(Self::request_timeout()),
+            ReadError::Parse(err) => Some(Self::parse_failure(&err)),
+            ReadError::Io(err) => {
+                println!("Failed to read from connection: {}", err);
+
+                Some(Self::internal_error(Some(format!("An internal error occurred while reading the request: {err}"))))
+            },
         }
     }
 
-    pub fn func_not_found(stream: &mut TcpStream, method: Method, path: String) {
-        let message = format!(
-            "Function for method {method} and path {path} doesn't exist"
-            ).to_string();
-        let detail = format!(
-            "Function for method {method} and path {path} does not exist or probably wasn't implemented yet"
-            ).to_string();
+    /// Response for a handler that panicked.
+    pub fn handler_panicked() -> ResponseEntity {
+        println!("Function panicked");
 
-        println!("{}", &message);
+        Self::internal_error(Some(String::from("The handler failed while processing the request")))
+    }
+
+    pub fn method_not_allowed(method: Method, path: String, allowed: &[Method]) -> ResponseEntity {
+        let detail = format!("Method {method} is not allowed for path {path}, the accepted methods are listed in the Allow header");
+
+        println!("{}", &detail);
+
+        let mut response: ResponseEntity = ApiError::from_status(StatusCode::MethodNotAllowed, detail).with_instance(&path).into();
+        response.headers.insert(String::from("Allow"), allow_header(allowed));
+        response
+    }
+
+    /// Automatic answer to `OPTIONS` requests for paths without an `OPTIONS` handler.
+    pub fn options(allowed: &[Method]) -> ResponseEntity {
+        let headers = HashMap::from([(String::from("Allow"), allow_header(allowed))]);
+
+        ResponseEntity::new(None, None, headers, StatusCode::NoContent)
+    }
+
+    pub fn func_not_found(method: Method, path: String) -> ResponseEntity {
+        let detail = format!("Function for method {method} and path {path} does not exist or probably wasn't implemented yet");
+
+        println!("{}", &detail);
         println!("Returning default 404 message");
 
-        Self::not_found(stream, ReturnBody::new(None, message, detail));
-        
+        ApiError::from_status(StatusCode::NotFound, detail).with_instance(&path).into()
     }
-}
\ No newline at end of file
+}
+
+fn allow_header(allowed: &[Method]) -> String {
+    allowed.iter().map(|m| m.to_string()).collect::<Vec<String>>().join(", ")
+}
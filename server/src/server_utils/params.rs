@@ -0,0 +1,375 @@
+use serde::{de::{self, Visitor, DeserializeSeed, MapAccess, SeqAccess, IntoDeserializer, value::Error}, forward_to_deserialize_any};
+
+/// Deserializes path or query parameters into the type asked by an extractor.
+///
+/// Structs and maps are filled by name, tuples and sequences by position and any other type
+/// takes the single parameter there is. Values are parsed from their text with `FromStr`.
+/// A name sent more than once fills a `Vec` field with every value, other fields take the first.
+pub(in crate) struct ParamsDeserializer<'de> {
+    params: Vec<(&'de str, Vec<&'de str>)>,
+}
+
+impl<'de> ParamsDeserializer<'de> {
+    pub(in crate) fn new(pairs: &'de [(String, String)]) -> Self {
+        let mut params: Vec<(&str, Vec<&str>)> = vec![];
+
+        for (name, value) in pairs {
+            match params.iter_mut().find(|(n, _)| n == name) {
+                Some((_, values)) => values.push(value),
+                None => params.push((name, vec![value])),
+            }
+        }
+
+        Self { params }
+    }
+
+    fn single(self) -> Result<ValuesDeserializer<'de>, Error> {
+        match self.params.len() {
+            1 => Ok(ValuesDeserializer(self.params.into_iter().next().unwrap().1)),
+            len => Err(de::Error::custom(format!("expected a single parameter, found {len}"))),
+        }
+    }
+
+    fn access(self) -> ParamsAccess<'de> {
+        ParamsAccess { params: self.params.into_iter(), values: None }
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+                where V: Visitor<'de>
+            {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ParamsDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_map(self.access())
+    }
+
+    fn deserialize_struct<V>(self, _name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_seq(self.access())
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        if len != self.params.len() {
+            return Err(de::Error::invalid_length(self.params.len(), &format!("{len} parameters").as_str()));
+        }
+
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_single! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_option deserialize_identifier
+    }
+}
+
+struct ParamsAccess<'de> {
+    params: std::vec::IntoIter<(&'de str, Vec<&'de str>)>,
+    values: Option<Vec<&'de str>>,
+}
+
+impl<'de> MapAccess<'de> for ParamsAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+        where K: DeserializeSeed<'de>
+    {
+        match self.params.next() {
+            Some((key, values)) => {
+                self.values = Some(values);
+                seed.deserialize(ValueDeserializer(key)).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+        where V: DeserializeSeed<'de>
+    {
+        let values = self.values.take().ok_or_else(|| de::Error::custom("value requested before its key"))?;
+        seed.deserialize(ValuesDeserializer(values))
+    }
+}
+
+impl<'de> SeqAccess<'de> for ParamsAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+        where T: DeserializeSeed<'de>
+    {
+        match self.params.next() {
+            Some((_, values)) => seed.deserialize(ValuesDeserializer(values)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Every value sent for a parameter name.
+struct ValuesDeserializer<'de>(Vec<&'de str>);
+
+macro_rules! forward_to_first {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+                where V: Visitor<'de>
+            {
+                ValueDeserializer(self.0.first().copied().unwrap_or_default()).$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValuesDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_seq(de::value::SeqDeserializer::new(self.0.into_iter().map(ValueDeserializer)))
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        ValueDeserializer(self.0.first().copied().unwrap_or_default()).deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V>(self, _name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_first! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_unit deserialize_map deserialize_identifier deserialize_ignored_any
+    }
+}
+
+/// A single parameter value.
+struct ValueDeserializer<'de>(&'de str);
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+                where V: Visitor<'de>
+            {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.0.into_deserializer().deserialize_enum(name, variants, visitor)
+    }
+
+    parse_value! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_derive::Deserialize;
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Filter {
+        name: String,
+        limit: Option<u32>,
+        active: bool,
+    }
+
+    #[test]
+    fn fills_structs_by_name() {
+        let params = params(&[("active", "true"), ("name", "ferris")]);
+        let filter = Filter::deserialize(ParamsDeserializer::new(&params)).unwrap();
+
+        assert_eq!(filter, Filter { name: String::from("ferris"), limit: None, active: true });
+    }
+
+    #[test]
+    fn fills_tuples_by_position() {
+        let params = params(&[("user", "7"), ("post", "hello")]);
+
+        let (user, post) = <(u64, String)>::deserialize(ParamsDeserializer::new(&params)).unwrap();
+        assert_eq!((user, post.as_str()), (7, "hello"));
+
+        assert!(<(u64,)>::deserialize(ParamsDeserializer::new(&params)).is_err());
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Tags {
+        tag: Vec<String>,
+        page: Option<u32>,
+    }
+
+    #[test]
+    fn collects_repeated_names() {
+        let params = params(&[("tag", "a"), ("page", "2"), ("tag", "b")]);
+        let tags = Tags::deserialize(ParamsDeserializer::new(&params)).unwrap();
+
+        assert_eq!(tags, Tags { tag: vec![String::from("a"), String::from("b")], page: Some(2) });
+    }
+
+    #[test]
+    fn parses_single_values() {
+        assert_eq!(u64::deserialize(ParamsDeserializer::new(&params(&[("id", "42")]))).unwrap(), 42);
+        assert!(u64::deserialize(ParamsDeserializer::new(&params(&[("id", "abc")]))).is_err());
+    }
+}
@@ -0,0 +1,683 @@
+use std::{str::FromStr, io::{self, Read, Write}, net::TcpStream, time::{Duration, Instant}};
+
+use crate::{config::ServerConfig, model::{request::RequestObj, header_map::HeaderMap, enums::{method::Method, parse_error::ParseError}}};
+
+const MAX_CHUNK_LINE_SIZE: usize = 1024;
+const CONTINUE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+const FORBIDDEN_TRAILERS: [&str; 5] = ["Content-Length", "Transfer-Encoding", "Host", "Content-Type", "Trailer"];
+
+pub(in crate) enum ParseStatus {
+    /// A full request was parsed, together with the number of bytes it took from the buffer.
+    Complete(RequestObj<Vec<u8>>, usize),
+    /// The buffer doesn't hold a full request yet, more bytes must be read.
+    Partial,
+}
+
+/// What `parse_request` learned about a request in earlier calls, kept until it's complete.
+#[derive(Default)]
+pub(in crate) struct ParseProgress {
+    /// Chunks of the body decoded so far, kept so every read only decodes the bytes that arrived since.
+    body: Vec<u8>,
+    /// Offset of the next chunk size line from the start of the encoded body.
+    position: usize,
+    /// The head was received and the client waits for a `100 Continue` before sending the body.
+    expects_continue: bool,
+}
+
+pub(in crate) enum ReadError {
+    /// The client closed the connection, or let it idle, without starting a new request.
+    Closed,
+    /// The client stopped sending bytes in the middle of a request.
+    TimedOut,
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+/// Streams whose reads can be limited in time, so `read_request` can stop at its deadline.
+pub(in crate) trait ReadTimeout {
+    fn set_read_timeout(&mut self, timeout: Duration) -> io::Result<()>;
+}
+
+impl ReadTimeout for TcpStream {
+    fn set_read_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, Some(timeout))
+    }
+}
+
+/// Reads from `stream` until a full request has been received, writing a `100 Continue` to it
+/// when the client waits for one before sending the body.
+///
+/// The whole request has to arrive within `request_timeout` from the call, however the bytes
+/// are spread, so a client trickling them can't hold the connection forever.
+///
+/// `buffer` must be kept between calls for the same connection, bytes received after the end
+/// of a request (pipelined requests) stay in it and are parsed first on the next call.
+pub(in crate) fn read_request<R>(stream: &mut R, buffer: &mut Vec<u8>, config: &ServerConfig) -> Result<RequestObj<Vec<u8>>, ReadError>
+    where R: Read + Write + ReadTimeout
+{
+    let deadline = Instant::now() + config.request_timeout;
+    let mut chunk = [0_u8; 4096];
+    let mut progress = ParseProgress::default();
+    let mut continued = false;
+
+    loop {
+        if let Some(request) = take_request(buffer, config, &mut progress)? {
+            return Ok(request);
+        }
+
+        if progress.expects_continue && !continued {
+            stream.write_all(CONTINUE).map_err(ReadError::Io)?;
+            continued = true;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Err(idle_error(buffer));
+        }
+
+        stream.set_read_timeout(remaining).map_err(ReadError::Io)?;
+
+        let size = match stream.read(&mut chunk) {
+            Ok(size) => size,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Err(idle_error(buffer)),
+            Err(e) => return Err(ReadError::Io(e)),
+        };
+
+        if size == 0 {
+            return Err(eof_error(buffer));
+        }
+
+        buffer.extend_from_slice(&chunk[..size]);
+    }
+}
+
+/// Same as `read_request` for the async server.
+#[cfg(feature = "async")]
+pub(in crate) async fn read_request_async<R>(stream: &mut R, buffer: &mut Vec<u8>, config: &ServerConfig) -> Result<RequestObj<Vec<u8>>, ReadError>
+    where R: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin
+{
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let deadline = tokio::time::Instant::now() + config.request_timeout;
+    let mut chunk = [0_u8; 4096];
+    let mut progress = ParseProgress::default();
+    let mut continued = false;
+
+    loop {
+        if let Some(request) = take_request(buffer, config, &mut progress)? {
+            return Ok(request);
+        }
+
+        if progress.expects_continue && !continued {
+            stream.write_all(CONTINUE).await.map_err(ReadError::Io)?;
+            continued = true;
+        }
+
+        let size = match tokio::time::timeout_at(deadline, stream.read(&mut chunk)).await {
+            Ok(Ok(size)) => size,
+            Ok(Err(e)) => return Err(ReadError::Io(e)),
+            Err(_) => return Err(idle_error(buffer)),
+        };
+
+        if size == 0 {
+            return Err(eof_error(buffer));
+        }
+
+        buffer.extend_from_slice(&chunk[..size]);
+    }
+}
+
+fn take_request(buffer: &mut Vec<u8>, config: &ServerConfig, progress: &mut ParseProgress) -> Result<Option<RequestObj<Vec<u8>>>, ReadError> {
+    match parse_request(buffer, config, progress).map_err(ReadError::Parse)? {
+        ParseStatus::Complete(request, consumed) => {
+            buffer.drain(..consumed);
+            Ok(Some(request))
+        },
+        ParseStatus::Partial => Ok(None),
+    }
+}
+
+fn is_idle(buffer: &[u8]) -> bool {
+    buffer.iter().all(|b| *b == b'\r' || *b == b'\n')
+}
+
+fn idle_error(buffer: &[u8]) -> ReadError {
+    match is_idle(buffer) {
+        true => ReadError::Closed,
+        false => ReadError::TimedOut,
+    }
+}
+
+fn eof_error(buffer: &[u8]) -> ReadError {
+    match is_idle(buffer) {
+        true => ReadError::Closed,
+        false => ReadError::Parse(ParseError::InvalidRequest),
+    }
+}
+
+/// Parses a HTTP/1.x request from the start of `buffer`.
+///
+/// When it returns `Partial` the caller should read more bytes into the same buffer and call
+/// it again with the same `progress`, which holds the chunks of the body decoded so far.
+pub(in crate) fn parse_request(buffer: &[u8], config: &ServerConfig, progress: &mut ParseProgress) -> Result<ParseStatus, ParseError> {
+    // RFC 9112 section 2.2: empty lines received before the request line should be ignored,
+    // they still count toward the head size, or a client could send them forever
+    let start = skip_leading_empty_lines(buffer);
+    let buffer = &buffer[start..];
+
+    let head_end = match find_head_end(buffer) {
+        Some(i) => i,
+        None if start + buffer.len() > config.max_header_size => return Err(ParseError::HeadersTooLarge),
+        None => return Ok(ParseStatus::Partial),
+    };
+
+    if start + head_end + 4 > config.max_header_size {
+        return Err(ParseError::HeadersTooLarge);
+    }
+
+    let head = std::str::from_utf8(&buffer[..head_end]).map_err(|_| ParseError::InvalidEncoding)?;
+    let mut lines = head.split("\r\n");
+
+    let (method, path, version) = parse_request_line(lines.next().unwrap_or_default())?;
+
+    let mut headers = HeaderMap::new();
+    let mut content_length: Option<usize> = None;
+
+    for line in lines {
+        let (key, value) = parse_header_line(line)?;
+
+        if key.eq_ignore_ascii_case("Content-Length") {
+            let length = parse_content_length(value)?;
+
+            match content_length {
+                Some(l) if l != length => return Err(ParseError::InvalidContentLength),
+                _ => content_length = Some(length),
+            }
+        }
+
+        headers.append(key, value);
+    }
+
+    let chunked = is_chunked(&headers)?;
+
+    // A message with both framings is a request smuggling vector, RFC 9112 section 6.3 allows rejecting it
+    if chunked && content_length.is_some() {
+        return Err(ParseError::InvalidRequest);
+    }
+
+    // RFC 9110 section 10.1.1: the client may wait for an interim response before sending the body,
+    // a body too large for the limit is rejected below before it's sent
+    progress.expects_continue = version == "HTTP/1.1"
+        && (chunked || content_length.unwrap_or(0) > 0)
+        && headers.get_all("Expect").iter().any(|e| e.eq_ignore_ascii_case("100-continue"));
+
+    let body_start = head_end + 4;
+
+    let (body, body_end) = if chunked {
+        match decode_chunked(&buffer[body_start..], &mut headers, config, progress)? {
+            Some((body, size)) => (body, body_start + size),
+            None => return Ok(ParseStatus::Partial),
+        }
+    } else {
+        let content_length = content_length.unwrap_or(0);
+
+        if content_length > config.max_body_size {
+            return Err(ParseError::BodyTooLarge);
+        }
+
+        let body_end = body_start + content_length;
+
+        if buffer.len() < body_end {
+            return Ok(ParseStatus::Partial);
+        }
+
+        (buffer[body_start..body_end].to_vec(), body_end)
+    };
+
+    Ok(ParseStatus::Complete(RequestObj::new(path, method, version, headers, body), start + body_end))
+}
+
+fn is_chunked(headers: &HeaderMap) -> Result<bool, ParseError> {
+    if !headers.contains_key("Transfer-Encoding") {
+        return Ok(false);
+    }
+
+    let codings: Vec<String> = headers
+        .get_all("Transfer-Encoding")
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(|c| c.trim().to_ascii_lowercase())
+        .filter(|c| !c.is_empty())
+        .collect();
+
+    match codings.as_slice() {
+        [coding] if coding == "chunked" => Ok(true),
+        [] => Err(ParseError::InvalidHeader),
+        _ => Err(ParseError::UnsupportedTransferEncoding),
+    }
+}
+
+/// Decodes a chunked body, appending its trailer fields to `headers`.
+///
+/// Returns `None` while the final chunk and the trailer section haven't been received yet,
+/// otherwise the decoded body and the amount of bytes the encoded body took. Complete chunks
+/// are moved into `progress` so they aren't decoded again on the next call.
+fn decode_chunked(buffer: &[u8], headers: &mut HeaderMap, config: &ServerConfig, progress: &mut ParseProgress) -> Result<Option<(Vec<u8>, usize)>, ParseError> {
+    let mut position = progress.position;
+
+    loop {
+        let line_end = match find_line_end(&buffer[position..]) {
+            Some(i) => position + i,
+            None if buffer.len() - position > MAX_CHUNK_LINE_SIZE => return Err(ParseError::InvalidChunk),
+            None => return Ok(None),
+        };
+
+        let size = parse_chunk_size(&buffer[position..line_end])?;
+        position = line_end + 2;
+
+        if size == 0 {
+            break;
+        }
+
+        if size > config.max_body_size.saturating_sub(progress.body.len()) {
+            return Err(ParseError::BodyTooLarge);
+        }
+
+        let chunk_end = position.checked_add(size).and_then(|end| end.checked_add(2)).ok_or(ParseError::BodyTooLarge)?;
+
+        if buffer.len() < chunk_end {
+            return Ok(None);
+        }
+
+        if &buffer[chunk_end - 2..chunk_end] != b"\r\n" {
+            return Err(ParseError::InvalidChunk);
+        }
+
+        progress.body.extend_from_slice(&buffer[position..chunk_end - 2]);
+        progress.position = chunk_end;
+        position = chunk_end;
+    }
+
+    let trailers_start = position;
+
+    loop {
+        let line_end = match find_line_end(&buffer[position..]) {
+            Some(i) => position + i,
+            None if buffer.len() - trailers_start > config.max_header_size => return Err(ParseError::HeadersTooLarge),
+            None => return Ok(None),
+        };
+
+        let line = std::str::from_utf8(&buffer[position..line_end]).map_err(|_| ParseError::InvalidEncoding)?;
+        position = line_end + 2;
+
+        if line.is_empty() {
+            return Ok(Some((std::mem::take(&mut progress.body), position)));
+        }
+
+        let (key, value) = parse_header_line(line)?;
+
+        // Fields that frame or route the message are not allowed to be changed by the trailer
+        if !FORBIDDEN_TRAILERS.iter().any(|f| key.eq_ignore_ascii_case(f)) {
+            headers.append(key, value);
+        }
+    }
+}
+
+fn parse_chunk_size(line: &[u8]) -> Result<usize, ParseError> {
+    let line = std::str::from_utf8(line).map_err(|_| ParseError::InvalidChunk)?;
+    // Chunk extensions are allowed after the size but we don't use any of them
+    let size = line.split(';').next().unwrap_or_default().trim_end_matches([' ', '\t']);
+
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ParseError::InvalidChunk);
+    }
+
+    usize::from_str_radix(size, 16).map_err(|_| ParseError::InvalidChunk)
+}
+
+fn find_line_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(2).position(|w| w == b"\r\n")
+}
+
+fn skip_leading_empty_lines(buffer: &[u8]) -> usize {
+    let mut i = 0;
+
+    while buffer[i..].starts_with(b"\r\n") {
+        i += 2;
+    }
+
+    i
+}
+
+fn find_head_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|w| w == b"\r\n\r\n")
+}
+
+fn parse_request_line(line: &str) -> Result<(Method, String, String), ParseError> {
+    let mut parts = line.split(' ');
+
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(t), Some(v), None) if !m.is_empty() && !t.is_empty() => (m, t, v),
+        _ => return Err(ParseError::InvalidRequest),
+    };
+
+    if !method.bytes().all(is_token_char) {
+        return Err(ParseError::InvalidRequest);
+    }
+
+    let method = Method::from_str(method).map_err(|_| ParseError::InvalidMethod)?;
+
+    if target.bytes().any(|b| b.is_ascii_control() || b == b' ') {
+        return Err(ParseError::InvalidRequest);
+    }
+
+    if version != "HTTP/1.1" && version != "HTTP/1.0" {
+        return Err(ParseError::InvalidProtocol);
+    }
+
+    Ok((method, target.to_string(), version.to_string()))
+}
+
+fn parse_header_line(line: &str) -> Result<(&str, &str), ParseError> {
+    // Obsolete line folding is rejected as allowed by RFC 9112 section 5.2
+    if line.starts_with(' ') || line.starts_with('\t') {
+        return Err(ParseError::InvalidHeader);
+    }
+
+    let (key, value) = line.split_once(':').ok_or(ParseError::InvalidHeader)?;
+
+    if key.is_empty() || !key.bytes().all(is_token_char) {
+        return Err(ParseError::InvalidHeader);
+    }
+
+    let value = value.trim_matches(|c| c == ' ' || c == '\t');
+
+    if value.bytes().any(|b| b.is_ascii_control() && b != b'\t') {
+        return Err(ParseError::InvalidHeader);
+    }
+
+    Ok((key, value))
+}
+
+fn parse_content_length(value: &str) -> Result<usize, ParseError> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseError::InvalidContentLength);
+    }
+
+    value.parse::<usize>().map_err(|_| ParseError::InvalidContentLength)
+}
+
+fn is_token_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(buffer: &[u8]) -> Result<ParseStatus, ParseError> {
+        parse_with(buffer, &ServerConfig::default())
+    }
+
+    fn parse_with(buffer: &[u8], config: &ServerConfig) -> Result<ParseStatus, ParseError> {
+        parse_request(buffer, config, &mut ParseProgress::default())
+    }
+
+    fn complete(buffer: &[u8]) -> RequestObj<Vec<u8>> {
+        match parse(buffer) {
+            Ok(ParseStatus::Complete(request, _)) => request,
+            Ok(ParseStatus::Partial) => panic!("request was only partially parsed"),
+            Err(e) => panic!("failed to parse request: {}", e),
+        }
+    }
+
+    #[test]
+    fn parses_request_with_body() {
+        let raw = b"POST /hello HTTP/1.1\r\nHost: localhost\r\nContent-Length: 16\r\n\r\n{\"name\":\"John\"}\n";
+        let request = complete(raw);
+
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.path, "/hello");
+        assert_eq!(request.headers.get("host"), Some("localhost"));
+        assert_eq!(request.body, b"{\"name\":\"John\"}\n");
+    }
+
+    #[test]
+    fn header_with_brace_is_not_a_body() {
+        let raw = b"GET /hello HTTP/1.1\r\nX-Data: {not json}\r\n\r\n";
+        let request = complete(raw);
+
+        assert_eq!(request.headers.get("X-Data"), Some("{not json}"));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn collects_repeated_headers() {
+        let request = complete(b"GET / HTTP/1.1\r\nAccept: text/html\r\naccept: application/json\r\n\r\n");
+
+        assert_eq!(request.headers.get_all("Accept"), ["text/html", "application/json"]);
+    }
+
+    #[test]
+    fn keeps_non_json_body() {
+        let request = complete(b"PUT /file HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello");
+
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[test]
+    fn incomplete_requests_are_partial() {
+        assert!(matches!(parse(b"GET /hello HTTP/1.1\r\nHost: x"), Ok(ParseStatus::Partial)));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc"),
+            Ok(ParseStatus::Partial)
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        let cases: Vec<(&[u8], ParseError)> = vec![
+            (b"GET /hello\r\n\r\n", ParseError::InvalidRequest),
+            (b"FETCH /hello HTTP/1.1\r\n\r\n", ParseError::InvalidMethod),
+            (b"GET /hello HTTP/2.0\r\n\r\n", ParseError::InvalidProtocol),
+            (b"GET / HTTP/1.1\r\nNo colon\r\n\r\n", ParseError::InvalidHeader),
+            (b"GET / HTTP/1.1\r\nBad Name: x\r\n\r\n", ParseError::InvalidHeader),
+            (b"GET / HTTP/1.1\r\nA: b\r\n folded\r\n\r\n", ParseError::InvalidHeader),
+            (b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n", ParseError::InvalidContentLength),
+            (b"POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab", ParseError::InvalidContentLength),
+            (b"GET /\xff HTTP/1.1\r\n\r\n", ParseError::InvalidEncoding),
+        ];
+
+        for (raw, expected) in cases {
+            match parse(raw) {
+                Err(e) => assert_eq!(e, expected, "{}", String::from_utf8_lossy(raw)),
+                Ok(_) => panic!("expected {} for {}", expected, String::from_utf8_lossy(raw)),
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_chunked_body_with_trailers() {
+        let raw = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Checksum: abc\r\n\r\n";
+        let request = complete(raw);
+
+        assert_eq!(request.body, b"hello world");
+        assert_eq!(request.headers.get("X-Checksum"), Some("abc"));
+
+        for end in [raw.len() - 1, raw.len() - 20, 60] {
+            assert!(matches!(parse(&raw[..end]), Ok(ParseStatus::Partial)));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_chunked_bodies() {
+        let cases: Vec<(&[u8], ParseError)> = vec![
+            (b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n", ParseError::InvalidChunk),
+            (b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabcd\r\n", ParseError::InvalidChunk),
+            (b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\nffffffffffffffff\r\nb\r\n", ParseError::BodyTooLarge),
+            (b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n", ParseError::UnsupportedTransferEncoding),
+            (b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n", ParseError::InvalidRequest),
+        ];
+
+        for (raw, expected) in cases {
+            match parse(raw) {
+                Err(e) => assert_eq!(e, expected, "{}", String::from_utf8_lossy(raw)),
+                Ok(_) => panic!("expected {} for {}", expected, String::from_utf8_lossy(raw)),
+            }
+        }
+    }
+
+    #[test]
+    fn enforces_size_limits() {
+        let config = ServerConfig::default().with_max_header_size(40).with_max_body_size(4);
+
+        let long_header = b"GET / HTTP/1.1\r\nX-Long: aaaaaaaaaaaaaaaaaaaaaaaa\r\n\r\n";
+        assert!(matches!(parse_with(long_header, &config), Err(ParseError::HeadersTooLarge)));
+        assert!(matches!(parse_with(&long_header[..44], &config), Err(ParseError::HeadersTooLarge)));
+
+        let empty_lines = b"\r\n".repeat(21);
+        assert!(matches!(parse_with(&empty_lines, &config), Err(ParseError::HeadersTooLarge)));
+        assert!(matches!(parse_with(&[&empty_lines[..30], b"GET / HTTP/1.1\r\n\r\n"].concat(), &config), Err(ParseError::HeadersTooLarge)));
+        assert!(matches!(parse_with(b"\r\n\r\nGET / HTTP/1.1\r\n\r\n", &config), Ok(ParseStatus::Complete(_, 22))));
+
+        let large_body = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n";
+        assert!(matches!(parse_with(large_body, &config), Err(ParseError::BodyTooLarge)));
+
+        let large_chunks = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\n";
+        let config = config.with_max_header_size(1024);
+        assert!(matches!(parse_with(large_chunks, &config), Err(ParseError::BodyTooLarge)));
+
+        let huge_chunk = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\na\r\n";
+        let config = config.with_max_body_size(usize::MAX);
+        assert!(matches!(parse_with(huge_chunk, &config), Err(ParseError::BodyTooLarge)));
+    }
+
+    /// Stream returning one segment per read, recording what the server writes to it.
+    struct Segments {
+        segments: Vec<&'static [u8]>,
+        written: Vec<u8>,
+        /// Amount of bytes written before each read.
+        written_at_read: Vec<usize>,
+    }
+
+    impl Segments {
+        fn new(segments: Vec<&'static [u8]>) -> Self {
+            Self { segments, written: vec![], written_at_read: vec![] }
+        }
+    }
+
+    impl Read for Segments {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.written_at_read.push(self.written.len());
+
+            if self.segments.is_empty() {
+                return Ok(0);
+            }
+
+            let segment = self.segments.remove(0);
+            buf[..segment.len()].copy_from_slice(segment);
+            Ok(segment.len())
+        }
+    }
+
+    impl ReadTimeout for Segments {
+        fn set_read_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Write for Segments {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reads_request_split_across_reads() {
+        let mut stream = Segments::new(vec![b"POST /hello HTTP/1.1\r\nCont", b"ent-Length: 11\r\n\r\nhello", b" world"]);
+        let mut buffer: Vec<u8> = vec![];
+        let request = match read_request(&mut stream, &mut buffer, &ServerConfig::default()) {
+            Ok(request) => request,
+            Err(_) => panic!("failed to read request"),
+        };
+
+        assert_eq!(request.body, b"hello world");
+        assert!(stream.written.is_empty());
+    }
+
+    #[test]
+    fn answers_expect_continue_before_the_body() {
+        let config = ServerConfig::default().with_max_body_size(16);
+
+        let mut stream = Segments::new(vec![b"PUT /file HTTP/1.1\r\nExpect: 100-continue\r\nTransfer-Encoding: chunked\r\n\r\n", b"5\r\nhello\r\n0\r\n\r\n"]);
+        let request = match read_request(&mut stream, &mut vec![], &config) {
+            Ok(request) => request,
+            Err(_) => panic!("failed to read request"),
+        };
+
+        assert_eq!(request.body, b"hello");
+        assert_eq!(stream.written, CONTINUE);
+        assert_eq!(stream.written_at_read, [0, CONTINUE.len()]);
+
+        // Too large to be accepted, the client gets the final status instead
+        let mut stream = Segments::new(vec![b"PUT /file HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 17\r\n\r\n"]);
+        assert!(matches!(read_request(&mut stream, &mut vec![], &config), Err(ReadError::Parse(ParseError::BodyTooLarge))));
+        assert!(stream.written.is_empty());
+
+        let mut stream = Segments::new(vec![b"PUT /file HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n", b"hello"]);
+        assert!(read_request(&mut stream, &mut vec![], &config).is_ok());
+        assert!(stream.written.is_empty());
+    }
+
+    #[test]
+    fn decodes_chunked_body_across_reads() {
+        let raw = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\nGET / HTTP/1.1\r\n\r\n";
+        let config = ServerConfig::default();
+        let mut progress = ParseProgress::default();
+
+        for end in [55, 60, 63, 70, 76] {
+            assert!(matches!(parse_request(&raw[..end], &config, &mut progress), Ok(ParseStatus::Partial)));
+        }
+
+        assert_eq!(progress.body, b"hello world");
+
+        match parse_request(raw, &config, &mut progress) {
+            Ok(ParseStatus::Complete(request, consumed)) => {
+                assert_eq!(request.body, b"hello world");
+                assert_eq!(&raw[consumed..], b"GET / HTTP/1.1\r\n\r\n");
+            },
+            _ => panic!("request wasn't complete"),
+        }
+    }
+
+    #[test]
+    fn reads_pipelined_requests_in_order() {
+        let mut stream = Segments::new(vec![b"GET /first HTTP/1.1\r\n\r\nPOST /second HTTP/1.1\r\nContent-Length: 2\r\n\r\nokGET /third HTTP/1.0\r\n\r\n"]);
+        let mut buffer: Vec<u8> = vec![];
+        let config = ServerConfig::default();
+
+        let paths: Vec<String> = (0..3)
+            .map(|_| match read_request(&mut stream, &mut buffer, &config) {
+                Ok(request) => request.path,
+                Err(_) => panic!("failed to read request"),
+            })
+            .collect();
+
+        assert_eq!(paths, ["/first", "/second", "/third"]);
+        assert!(matches!(read_request(&mut stream, &mut buffer, &config), Err(ReadError::Closed)));
+    }
+
+    #[test]
+    fn keep_alive_follows_http_version() {
+        assert!(complete(b"GET / HTTP/1.1\r\n\r\n").keep_alive());
+        assert!(!complete(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").keep_alive());
+        assert!(!complete(b"GET / HTTP/1.0\r\n\r\n").keep_alive());
+        assert!(complete(b"GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n").keep_alive());
+    }
+}
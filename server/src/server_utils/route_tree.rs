@@ -0,0 +1,472 @@
+use std::{collections::{HashMap, HashSet, hash_map::Entry}, str::FromStr};
+
+use regex::Regex;
+
+use crate::{model::enums::method::Method, router::RouteError, server_utils::server_utils::percent_decode};
+
+/// Segment trie mapping mounted paths to their handlers.
+///
+/// Every node is a path segment, so a lookup walks the request path once no matter how many
+/// routes are mounted. When more than one route matches, static segments win over parameters
+/// (`{id}`) and parameters win over catch-alls (`{*rest}`), falling back to the next kind when
+/// the rest of the path doesn't match. `/users/me` always beats `/users/{id}`.
+///
+/// Parameters can be constrained with a type (`{id:u64}`) or a regex (`{slug:[a-z-]+}`) that
+/// the whole segment has to match. Constrained parameters are tried before unconstrained ones,
+/// in the order they were mounted.
+///
+/// Two routes for the same method can't match exactly the same requests: `/a/{x}` and
+/// `/a/{y}` are rejected as conflicting, while `/a/{x:u64}` and `/a/{y}` aren't, since the
+/// constrained one goes first.
+pub(in crate) struct RouteTree<T> {
+    root: Node<T>,
+}
+
+struct Node<T> {
+    statics: HashMap<String, Node<T>>,
+    params: Vec<ParamNode<T>>,
+    // Catch-alls are always the last segment, so they hold the routes directly
+    catch_all: HashMap<Method, Route<T>>,
+    routes: HashMap<Method, Route<T>>,
+}
+
+struct Route<T> {
+    value: T,
+    // Path as it was mounted, to report conflicts
+    path: String,
+    // Parameter names in the order they appear in the path, the catch-all one included
+    params: Vec<String>,
+}
+
+struct ParamNode<T> {
+    constraint: Constraint,
+    node: Node<T>,
+}
+
+enum Constraint {
+    Any,
+    Type(String, fn(&str) -> bool),
+    Pattern(Regex),
+}
+
+enum Segment<'p> {
+    Static(&'p str),
+    Param(&'p str, Constraint),
+    CatchAll(&'p str),
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self { statics: HashMap::new(), params: vec![], catch_all: HashMap::new(), routes: HashMap::new() }
+    }
+}
+
+impl Constraint {
+    /// Parses the text after the `:` of a parameter, primitive type names are checked by
+    /// parsing the segment and anything else is a regex.
+    fn parse(constraint: Option<&str>) -> Result<Self, String> {
+        let constraint = match constraint {
+            Some(constraint) => constraint,
+            None => return Ok(Self::Any),
+        };
+
+        let parses: Option<fn(&str) -> bool> = match constraint {
+            "u8" => Some(parses::<u8>),
+            "u16" => Some(parses::<u16>),
+            "u32" => Some(parses::<u32>),
+            "u64" => Some(parses::<u64>),
+            "u128" => Some(parses::<u128>),
+            "usize" => Some(parses::<usize>),
+            "i8" => Some(parses::<i8>),
+            "i16" => Some(parses::<i16>),
+            "i32" => Some(parses::<i32>),
+            "i64" => Some(parses::<i64>),
+            "i128" => Some(parses::<i128>),
+            "isize" => Some(parses::<isize>),
+            "f32" => Some(parses::<f32>),
+            "f64" => Some(parses::<f64>),
+            "bool" => Some(parses::<bool>),
+            _ => None,
+        };
+
+        match parses {
+            Some(parses) => Ok(Self::Type(constraint.to_string(), parses)),
+            None => Regex::new(&format!("^(?:{constraint})$"))
+                .map(Self::Pattern)
+                .map_err(|err| format!("invalid constraint {constraint}: {err}")),
+        }
+    }
+
+    fn matches(&self, segment: &str) -> bool {
+        match self {
+            Self::Any => !segment.is_empty(),
+            Self::Type(_, parses) => parses(segment),
+            Self::Pattern(regex) => regex.is_match(segment),
+        }
+    }
+
+    fn source(&self) -> &str {
+        match self {
+            Self::Any => "",
+            Self::Type(name, _) => name,
+            Self::Pattern(regex) => regex.as_str(),
+        }
+    }
+}
+
+fn parses<T: FromStr>(segment: &str) -> bool {
+    segment.parse::<T>().is_ok()
+}
+
+
+impl<T> RouteTree<T> {
+    pub(in crate) fn new() -> Self {
+        Self { root: Node::default() }
+    }
+
+    /// Adds a route. Fails when the path is malformed or a route mounted before for the same
+    /// method matches the same requests, leaving the tree as it was.
+    pub(in crate) fn insert(&mut self, method: Method, path: &str, value: T) -> Result<(), RouteError> {
+        let invalid = |reason: String| RouteError::InvalidPath { path: path.to_string(), reason };
+
+        let segments = split_path(path);
+        let mut parsed = vec![];
+        let mut params: Vec<String> = vec![];
+
+        for (i, segment) in segments.iter().enumerate() {
+            let segment = parse_segment(segment).map_err(invalid)?;
+
+            if let Segment::Param(name, _) | Segment::CatchAll(name) = &segment {
+                if name.is_empty() {
+                    return Err(invalid(String::from("parameters need a name")));
+                }
+
+                if params.iter().any(|p| p == name) {
+                    return Err(invalid(format!("parameter {name} is used more than once")));
+                }
+
+                if matches!(segment, Segment::CatchAll(_)) && i != segments.len() - 1 {
+                    return Err(invalid(format!("catch-all parameter {{*{name}}} must be the last segment")));
+                }
+
+                params.push(name.to_string());
+            }
+
+            parsed.push(segment);
+        }
+
+        let mut node = &mut self.root;
+        let route = Route { value, path: path.to_string(), params };
+
+        for segment in parsed {
+            match segment {
+                Segment::Static(s) => node = node.statics.entry(s.to_string()).or_default(),
+                Segment::Param(_, constraint) => node = node.param_node(constraint),
+                Segment::CatchAll(_) => return insert_route(&mut node.catch_all, method, route),
+            }
+        }
+
+        insert_route(&mut node.routes, method, route)
+    }
+
+    /// Finds the route for `method` and `path`, along with the values of its path parameters
+    /// in the order they appear in the path.
+    pub(in crate) fn find(&self, method: &Method, path: &str) -> Option<(&T, Vec<(String, String)>)> {
+        let segments = decode_segments(path);
+        let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+        let mut captured = vec![];
+
+        let route = self.root.find(method, &segments, &mut captured)?;
+        let params = route.params.iter().cloned().zip(captured).collect();
+
+        Some((&route.value, params))
+    }
+
+    /// Methods mounted for `path` on any of the routes matching it, in the order of `METHODS`
+    /// (the order the `Allow` header lists them in).
+    pub(in crate) fn methods(&self, path: &str) -> Vec<Method> {
+        let segments = decode_segments(path);
+        let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+
+        let mut methods = HashSet::new();
+        self.root.collect_methods(&segments, &mut methods);
+
+        METHODS.iter().filter(|m| methods.contains(m)).cloned().collect()
+    }
+}
+
+fn insert_route<T>(routes: &mut HashMap<Method, Route<T>>, method: Method, route: Route<T>) -> Result<(), RouteError> {
+    match routes.entry(method) {
+        Entry::Occupied(entry) => Err(RouteError::Conflict {
+            method: entry.key().clone(),
+            path: route.path,
+            existing: entry.get().path.clone(),
+        }),
+        Entry::Vacant(entry) => {
+            entry.insert(route);
+            Ok(())
+        },
+    }
+}
+
+const METHODS: [Method; 9] = [
+    Method::GET, Method::HEAD, Method::POST, Method::PUT, Method::DELETE,
+    Method::CONNECT, Method::OPTIONS, Method::TRACE, Method::PATCH
+];
+
+impl<T> Node<T> {
+    /// Child for a parameter with `constraint`, shared by every route using the same one.
+    fn param_node(&mut self, constraint: Constraint) -> &mut Node<T> {
+        let position = match self.params.iter().position(|p| p.constraint.source() == constraint.source()) {
+            Some(position) => position,
+            None => {
+                // Unconstrained parameters stay last so they're only tried after the others
+                let position = match constraint {
+                    Constraint::Any => self.params.len(),
+                    _ => self.params.iter().position(|p| matches!(p.constraint, Constraint::Any)).unwrap_or(self.params.len()),
+                };
+
+                self.params.insert(position, ParamNode { constraint, node: Node::default() });
+                position
+            },
+        };
+
+        &mut self.params[position].node
+    }
+
+    fn find(&self, method: &Method, segments: &[&str], captured: &mut Vec<String>) -> Option<&Route<T>> {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return self.routes.get(method),
+        };
+
+        if let Some(route) = self.statics.get(*segment).and_then(|n| n.find(method, rest, captured)) {
+            return Some(route);
+        }
+
+        for param in self.params.iter().filter(|p| p.constraint.matches(segment)) {
+            captured.push(segment.to_string());
+
+            if let Some(route) = param.node.find(method, rest, captured) {
+                return Some(route);
+            }
+
+            captured.pop();
+        }
+
+        let route = self.catch_all.get(method)?;
+        captured.push(join_catch_all(segments));
+
+        Some(route)
+    }
+
+    fn collect_methods<'n>(&'n self, segments: &[&str], methods: &mut HashSet<&'n Method>) {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return methods.extend(self.routes.keys()),
+        };
+
+        if let Some(node) = self.statics.get(*segment) {
+            node.collect_methods(rest, methods);
+        }
+
+        for param in self.params.iter().filter(|p| p.constraint.matches(segment)) {
+            param.node.collect_methods(rest, methods);
+        }
+
+        methods.extend(self.catch_all.keys());
+    }
+}
+
+/// Splits a path into its segments, ignoring the leading and a single trailing slash.
+fn split_path(path: &str) -> Vec<&str> {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    let path = path.strip_suffix('/').unwrap_or(path);
+
+    match path.is_empty() {
+        true => vec![],
+        false => path.split('/').collect(),
+    }
+}
+
+/// Splits a request path and percent-decodes each segment on its own, so an encoded `/`
+/// doesn't start a new segment.
+fn decode_segments(path: &str) -> Vec<String> {
+    split_path(path).into_iter().map(|s| percent_decode(s, false)).collect()
+}
+
+/// Joins the decoded segments a catch-all captured. A `/` or `%` decoded from a segment is
+/// escaped again, so `a/b%2Fc` and `a/b/c` stay different values.
+fn join_catch_all(segments: &[&str]) -> String {
+    segments.iter()
+        .map(|s| s.replace('%', "%25").replace('/', "%2F"))
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn parse_segment(segment: &str) -> Result<Segment<'_>, String> {
+    match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+        Some(param) => match param.strip_prefix('*') {
+            Some(name) => Ok(Segment::CatchAll(name)),
+            None => {
+                let (name, constraint) = match param.split_once(':') {
+                    Some((name, constraint)) => (name, Some(constraint)),
+                    None => (param, None),
+                };
+
+                Ok(Segment::Param(name, Constraint::parse(constraint)?))
+            },
+        },
+        // `{name}.txt` would otherwise be matched literally, braces and all
+        None if segment.contains(['{', '}']) => Err(format!("parameter in {segment} must take the whole segment")),
+        None => Ok(Segment::Static(segment)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(paths: &[&'static str]) -> RouteTree<&'static str> {
+        let mut tree = RouteTree::new();
+
+        for path in paths {
+            tree.insert(Method::GET, path, *path).unwrap();
+        }
+
+        tree
+    }
+
+    fn matched(tree: &RouteTree<&'static str>, path: &str) -> Option<&'static str> {
+        tree.find(&Method::GET, path).map(|(route, _)| *route)
+    }
+
+    #[test]
+    fn static_segments_beat_params_and_catch_alls() {
+        let tree = tree(&["/users/{*rest}", "/users/{id}", "/users/me"]);
+
+        assert_eq!(matched(&tree, "/users/me"), Some("/users/me"));
+        assert_eq!(matched(&tree, "/users/42"), Some("/users/{id}"));
+        assert_eq!(matched(&tree, "/users/42/posts"), Some("/users/{*rest}"));
+    }
+
+    #[test]
+    fn falls_back_when_a_deeper_segment_does_not_match() {
+        let tree = tree(&["/users/me/settings", "/users/{id}/posts"]);
+
+        assert_eq!(matched(&tree, "/users/me/posts"), Some("/users/{id}/posts"));
+        assert_eq!(matched(&tree, "/users/me/settings"), Some("/users/me/settings"));
+        assert_eq!(matched(&tree, "/users/me/other"), None);
+        assert_eq!(matched(&tree, "/users/42/settings"), None);
+    }
+
+    #[test]
+    fn captures_path_parameters() {
+        let tree = tree(&["/users/{id}/posts/{post}", "/files/{*path}"]);
+
+        let (_, params) = tree.find(&Method::GET, "/users/7/posts/12/").unwrap();
+        assert_eq!(params, vec![(String::from("id"), String::from("7")), (String::from("post"), String::from("12"))]);
+
+        let (_, params) = tree.find(&Method::GET, "/files/css/site.css").unwrap();
+        assert_eq!(params, vec![(String::from("path"), String::from("css/site.css"))]);
+
+        assert!(tree.find(&Method::GET, "/files").is_none());
+        assert!(tree.find(&Method::POST, "/files/site.css").is_none());
+    }
+
+    #[test]
+    fn collects_methods_of_every_matching_route() {
+        let mut tree = RouteTree::new();
+        tree.insert(Method::DELETE, "/users/{id}", ()).unwrap();
+        tree.insert(Method::GET, "/users/me", ()).unwrap();
+        tree.insert(Method::PUT, "/users/{*rest}", ()).unwrap();
+
+        assert_eq!(tree.methods("/users/me"), vec![Method::GET, Method::PUT, Method::DELETE]);
+        assert_eq!(tree.methods("/users/42"), vec![Method::PUT, Method::DELETE]);
+        assert!(tree.methods("/posts").is_empty());
+    }
+
+    #[test]
+    fn checks_constraints_while_matching() {
+        let tree = tree(&["/users/{id:u64}", "/users/{slug:[a-z-]+}", "/users/{other}/posts", "/posts/{id:u32}"]);
+
+        assert_eq!(matched(&tree, "/users/42"), Some("/users/{id:u64}"));
+        assert_eq!(matched(&tree, "/users/ferris-crab"), Some("/users/{slug:[a-z-]+}"));
+        assert_eq!(matched(&tree, "/users/Ferris"), None);
+        assert_eq!(matched(&tree, "/users/42/posts"), Some("/users/{other}/posts"));
+        assert_eq!(matched(&tree, "/posts/-1"), None);
+
+        let (_, params) = tree.find(&Method::GET, "/users/ab-c").unwrap();
+        assert_eq!(params, vec![(String::from("slug"), String::from("ab-c"))]);
+    }
+
+    #[test]
+    fn accepts_capitalized_names() {
+        let tree = tree(&["/users/{userId}"]);
+
+        let (_, params) = tree.find(&Method::GET, "/users/7").unwrap();
+        assert_eq!(params, vec![(String::from("userId"), String::from("7"))]);
+    }
+
+    #[test]
+    fn decodes_request_segments() {
+        let tree = tree(&["/files/a b", "/users/{name}"]);
+
+        assert_eq!(matched(&tree, "/files/a%20b"), Some("/files/a b"));
+
+        let (_, params) = tree.find(&Method::GET, "/users/caf%C3%A9%2Fbar").unwrap();
+        assert_eq!(params, vec![(String::from("name"), String::from("café/bar"))]);
+    }
+
+    #[test]
+    fn catch_alls_keep_encoded_slashes_apart() {
+        let tree = tree(&["/files/{*path}"]);
+        let captured = |path: &str| tree.find(&Method::GET, path).unwrap().1.remove(0).1;
+
+        assert_eq!(captured("/files/a/b/c"), "a/b/c");
+        assert_eq!(captured("/files/a/b%2Fc"), "a/b%2Fc");
+        assert_eq!(captured("/files/a%20b/100%25"), "a b/100%25");
+    }
+
+    #[test]
+    fn matches_the_root() {
+        let tree = tree(&["/"]);
+
+        assert_eq!(matched(&tree, "/"), Some("/"));
+        assert_eq!(matched(&tree, "/other"), None);
+    }
+
+    #[test]
+    fn rejects_routes_matching_the_same_requests() {
+        let mut tree = tree(&["/users/{id}", "/users/{id:u64}/posts", "/files/{*path}"]);
+
+        let conflict = |path: &str, existing: &str| Err(RouteError::Conflict { method: Method::GET, path: path.to_string(), existing: existing.to_string() });
+
+        assert_eq!(tree.insert(Method::GET, "/users/{name}", "dup"), conflict("/users/{name}", "/users/{id}"));
+        assert_eq!(tree.insert(Method::GET, "/users/{user:u64}/posts/", "dup"), conflict("/users/{user:u64}/posts/", "/users/{id:u64}/posts"));
+        assert_eq!(tree.insert(Method::GET, "/files/{*rest}", "dup"), conflict("/files/{*rest}", "/files/{*path}"));
+
+        // Other methods and other constraints aren't conflicts
+        assert!(tree.insert(Method::POST, "/users/{name}", "post").is_ok());
+        assert!(tree.insert(Method::GET, "/users/{id:u64}", "typed").is_ok());
+        assert_eq!(matched(&tree, "/users/abc"), Some("/users/{id}"));
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        let mut tree = RouteTree::new();
+
+        let reason = |path: &str, tree: &mut RouteTree<()>| match tree.insert(Method::GET, path, ()) {
+            Err(RouteError::InvalidPath { reason, .. }) => reason,
+            other => panic!("{path} was not rejected: {other:?}"),
+        };
+
+        assert!(reason("/files/{*path}/raw", &mut tree).contains("last segment"));
+        assert!(reason("/users/{id}/posts/{id}", &mut tree).contains("more than once"));
+        assert!(reason("/users/{id:[a-z}", &mut tree).contains("invalid constraint"));
+        assert!(reason("/users/{}", &mut tree).contains("name"));
+        assert!(reason("/files/{name}.txt", &mut tree).contains("whole segment"));
+        assert!(reason("/files/v{version}", &mut tree).contains("whole segment"));
+        assert!(tree.methods("/users/1").is_empty());
+    }
+}
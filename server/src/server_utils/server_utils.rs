@@ -1,197 +1,107 @@
-use std::collections::HashMap;
-
-use regex::Regex;
-
-use crate::{model::{request::RequestObj, Request}, server::{Server, Endpoint}};
-
-pub(in crate) fn process_buffer(buffer: &Vec<u8>) -> Vec<Vec<u8>> {
-    enum BufferSteps {
-        FindingSpace,
-        SpaceFound,
-        Json,
-    }
-
-    let mut processed_vec: Vec<Vec<u8>> = vec![];
-    let mut temp_vec: Vec<u8> = vec![];
-
-    let mut current_step = BufferSteps::FindingSpace;
-
-    for (i, byte) in buffer.iter().enumerate() {
-        match current_step {
-            BufferSteps::FindingSpace => {
-                if *byte == 13 as u8 && buffer[i + 1] == 10 as u8 {
-                    current_step = BufferSteps::SpaceFound;
-                } else if *byte == 123 {
-                    current_step = BufferSteps::Json;
-                } else {
-                    temp_vec.push(*byte)
-                }
-            }
-            BufferSteps::SpaceFound => {
-                if temp_vec.len() > 0 {
-                    processed_vec.push(temp_vec.clone());
-                }
-
-                if *byte != 13 as u8 && *byte != 10 as u8 {
-                    if *byte == 123 {
-                        temp_vec.push(*byte);
-                        current_step = BufferSteps::Json;
-                        continue;
-                    } else {
-                        temp_vec.push(*byte);
-                        current_step = BufferSteps::FindingSpace;
-                        continue;
-                    }
-                }
-
-                temp_vec.clear();
-            }
-            BufferSteps::Json => {
-                temp_vec = buffer[(i - 1)..(buffer.len() - 1)].to_vec();
-                processed_vec.push(temp_vec.clone());
-
-                break;
-            }
-        }
-    }
-
-    return processed_vec;
+use crate::{server::{RouteTable, RouteHandler}, model::enums::method::Method};
+
+/// Outcome of looking a request up in the `RouteTable`.
+pub(in crate) enum Routed<'r> {
+    Handler(&'r RouteHandler, Vec<(String, String)>),
+    /// `OPTIONS` request without a mounted handler, answered with the allowed methods
+    Options(Vec<Method>),
+    /// The path exists but not for the request method
+    MethodNotAllowed(Vec<Method>),
+    NotFound,
 }
 
-pub(in crate) fn buffer_to_request(processed_buf: &Vec<Vec<u8>>) -> RequestObj<String> {
-    let mut path = String::new();
-    let mut method = String::new();
-    let headers: HashMap<String, String> = HashMap::new();
-    let mut body = String::new();
+impl RouteTable {
+    /// Resolves the request to a handler. `HEAD` requests use the `GET` handler unless one was
+    /// mounted for `HEAD` and `OPTIONS` ones are answered automatically.
+    pub(in crate) fn route(&self, method: &Method, path: &str) -> Routed<'_> {
+        let found = match method {
+            Method::HEAD => self.find(method, path).or_else(|| self.find(&Method::GET, path)),
+            _ => self.find(method, path),
+        };
+
+        if let Some((handler, params)) = found {
+            return Routed::Handler(handler, params);
+        }
 
-    let processed_buf_size = &processed_buf.len() - 1;
+        let mut allowed = self.tree.methods(route_path(path));
 
-    for (i, buf) in processed_buf.iter().enumerate() {
-        if i == processed_buf.len() - 1 {
-            let filtered_end: Vec<(usize, &u8)> = buf
-                .iter()
-                .enumerate()
-                .filter(|(_i, f)| **f == 125 as u8)
-                .collect();
-            let last_index: usize = filtered_end.iter().last().unwrap().0;
+        if allowed.is_empty() {
+            return Routed::NotFound;
+        }
 
-            body = String::from_utf8_lossy(&buf[0..last_index + 1]).to_string();
+        if allowed.contains(&Method::GET) && !allowed.contains(&Method::HEAD) {
+            allowed.push(Method::HEAD);
+        }
+        if !allowed.contains(&Method::OPTIONS) {
+            allowed.push(Method::OPTIONS);
         }
 
-        if i == 0 {
-            let str = String::from_utf8_lossy(buf);
-
-            str.split(' ')
-                .into_iter()
-                .enumerate()
-                .for_each(|(i, f)| match i {
-                    0 => method = f.to_string(),
-                    1 => path = f.to_string(),
-                    _ => {}
-                });
-        } else if i < processed_buf_size {
-            let mut key = String::new();
-            let mut value = String::new();
-
-            String::from_utf8_lossy(buf)
-                .split(':')
-                .into_iter()
-                .enumerate()
-                .for_each(|(i, f)| match i {
-                    0 => key = f.trim().to_string(),
-                    1 => value = f.trim().to_string(),
-                    _ => {}
-                })
+        match method {
+            Method::OPTIONS => Routed::Options(allowed),
+            _ => Routed::MethodNotAllowed(allowed),
         }
     }
 
-    RequestObj::new(path, method, headers, body)
+    /// Finds the handler for a request, returning it with the path parameters.
+    pub(in crate) fn find(&self, method: &Method, path: &str) -> Option<(&RouteHandler, Vec<(String, String)>)> {
+        self.tree.find(method, route_path(path))
+    }
 }
 
-impl<'s, Req> Server<Req> 
-    where Req: Request 
-{
-    pub(in crate) fn parse_path_return_func(&self, endpoint: Endpoint) -> (Option<Endpoint>, HashMap<String, String>) {
-        let mut path_vec = endpoint.path.clone();
-        let method = endpoint.method.clone();
-
-        let path_param_regex = Regex::new("\\{([^A-Z]*?)\\}").unwrap();
-
-        let query_params: HashMap<String, String> = if path_vec.last().unwrap().contains('?') {
-            parse_query_params(path_vec.last().unwrap().split('?').last().unwrap().to_string())
-        } else { HashMap::new() };
-
-        if path_vec.last().unwrap() == "" && path_vec.len() > 1 {
-            path_vec.remove(path_vec.len() - 1);
-        }
-
-        let mut same_vec: Option<Endpoint> = None;
-        let mut endpoint: Endpoint;
-
-        let mut possible = false;
-        let mut has_param = false;
-
-        for (e, _value) in &self.funcs {
-            if e.path.len() == path_vec.len() {
-                endpoint = e.clone();
-                for (i, s) in path_vec.iter().enumerate() {
-                    let current_element = endpoint.path.get(i).unwrap();
-                    if s != current_element {
-                        if path_param_regex.is_match(current_element) {
-                            possible = true;
-                            has_param = true;
-                        } else {
-                            possible = false;
-                            has_param = false;
-                        }
-                    } else {
-                        possible = true;
-                    }
-                }
-
-                if e.method == method && possible {
-                    same_vec = Some(endpoint);
-                } else {
-                    possible = false;
-                }
-            }
-        }
+/// Path of a request target without its query string and fragment.
+pub(in crate) fn route_path(target: &str) -> &str {
+    target.split(['?', '#']).next().unwrap_or_default()
+}
 
-        if let Some(e) = same_vec {
-            let mut params: HashMap<String, String> = if let true = !query_params.is_empty() {
-                query_params
-            } else { HashMap::new() };
-            
-            if has_param {
-                for (i, endpoint) in e.path.iter().enumerate() {
-                    if path_param_regex.is_match(endpoint) {
-                        params.insert(
-                            endpoint.replace(&['{', '}'], ""),
-                            path_vec.get(i).unwrap().to_string(),
-                        );
-                    }
-                }
-            }
-
-            return (Some(e.clone()), params);
+/// Decodes `%XX` escapes, and `+` as a space when `plus_as_space` is set like in form encoded
+/// query strings. Malformed escapes are kept as they are and invalid UTF-8 is replaced.
+pub(in crate) fn percent_decode(input: &str, plus_as_space: bool) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = match bytes[i] {
+            b'%' => bytes.get(i + 1..i + 3).and_then(|hex| Some(hex_value(hex[0])? * 16 + hex_value(hex[1])?)),
+            _ => None,
+        };
+
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            },
+            (None, b'+') if plus_as_space => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
         }
 
-        (None, HashMap::new())
+        i += 1;
     }
 
+    String::from_utf8_lossy(&decoded).to_string()
 }
 
-pub(in crate) fn parse_query_params(query: String) -> HashMap<String, String> {
-    let query_parsed: Vec<String> = query.split('&')
-        .map(|q| q.to_string()).collect();
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|d| d as u8)
+}
 
-    let mut query_map: HashMap<String, String> = HashMap::new();
-    
-    for q in query_parsed {
-        let item: Vec<String> = q.split('=').map(|f| f.to_string()).collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        query_map.insert(item.first().unwrap().to_string(), item.last().unwrap().to_string());
+    #[test]
+    fn strips_query_and_fragment() {
+        assert_eq!(route_path("/hello?name=x#top"), "/hello");
+        assert_eq!(route_path("/hello#top?name=x"), "/hello");
+        assert_eq!(route_path("/hello"), "/hello");
     }
 
-    query_map
-}
\ No newline at end of file
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("caf%C3%A9+au%20lait", true), "café au lait");
+        assert_eq!(percent_decode("a+b%2Bc", false), "a+b+c");
+        assert_eq!(percent_decode("100%", true), "100%");
+        assert_eq!(percent_decode("%zz%4", true), "%zz%4");
+    }
+}
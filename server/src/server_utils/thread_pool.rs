@@ -0,0 +1,175 @@
+use std::{sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}, mpsc::{self, Receiver, SyncSender}}, thread::{self, JoinHandle}, panic::{self, AssertUnwindSafe}};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Fixed amount of worker threads consuming jobs from a bounded queue.
+///
+/// `execute` blocks while the queue is full, so the accept loop stops taking new connections
+/// until a worker is free and the pending ones wait in the listener backlog instead.
+pub(in crate) struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<SyncSender<Job>>,
+    backlog: Backlog,
+}
+
+/// Amount of jobs sent to a `ThreadPool` that no worker picked up yet.
+#[derive(Clone, Debug, Default)]
+pub(in crate) struct Backlog(Arc<AtomicUsize>);
+
+impl Backlog {
+    pub(in crate) fn is_empty(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == 0
+    }
+}
+
+impl ThreadPool {
+    pub(in crate) fn new(size: usize, queue_size: usize) -> Self {
+        let size = size.max(1);
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let backlog = Backlog::default();
+
+        let workers = (0..size)
+            .map(|id| Worker::new(id, Arc::clone(&receiver), backlog.clone()))
+            .collect();
+
+        Self { workers, sender: Some(sender), backlog }
+    }
+
+    pub(in crate) fn backlog(&self) -> Backlog {
+        self.backlog.clone()
+    }
+
+    pub(in crate) fn execute<F>(&self, job: F)
+        where F: FnOnce() + Send + 'static
+    {
+        if let Some(sender) = &self.sender {
+            // Counted before sending so it includes a job blocked on a full queue
+            self.backlog.0.fetch_add(1, Ordering::SeqCst);
+
+            if sender.send(Box::new(job)).is_err() {
+                self.backlog.0.fetch_sub(1, Ordering::SeqCst);
+                println!("Failed to send job, every worker has stopped");
+            }
+        }
+    }
+
+    /// Closes the queue without waiting for the workers, each one stops on its own once the
+    /// queue is empty and its current job finished.
+    pub(in crate) fn detach(mut self) {
+        drop(self.sender.take());
+        self.workers.clear();
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // Closing the channel makes every worker leave its loop once the queue is empty
+        drop(self.sender.take());
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                if thread.join().is_err() {
+                    println!("Worker {} stopped with an error", worker.id);
+                }
+            }
+        }
+    }
+}
+
+struct Worker {
+    id: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(id: usize, receiver: Arc<Mutex<Receiver<Job>>>, backlog: Backlog) -> Self {
+        let thread = thread::Builder::new()
+            .name(format!("worker-{id}"))
+            .spawn(move || loop {
+                let job = match receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => break,
+                };
+
+                match job {
+                    Ok(job) => {
+                        backlog.0.fetch_sub(1, Ordering::SeqCst);
+
+                        // A panicking handler must not take the worker down with it
+                        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            println!("Worker {id} recovered from a panic");
+                        }
+                    },
+                    Err(_) => break,
+                }
+            })
+            .expect("failed to spawn worker thread");
+
+        Self { id, thread: Some(thread) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn runs_every_job_before_dropping() {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        {
+            let pool = ThreadPool::new(4, 2);
+
+            for _ in 0..32 {
+                let counter = Arc::clone(&counter);
+                pool.execute(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        }
+
+        assert_eq!(counter.load(Ordering::SeqCst), 32);
+    }
+
+    #[test]
+    fn survives_panicking_jobs() {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        {
+            let pool = ThreadPool::new(1, 1);
+            pool.execute(|| panic!("handler failed"));
+
+            let c = Arc::clone(&counter);
+            pool.execute(move || {
+                c.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn counts_jobs_waiting_for_a_worker() {
+        let pool = ThreadPool::new(1, 2);
+        let backlog = pool.backlog();
+        let (release, on_release) = channel::<()>();
+        let (started, on_start) = channel::<()>();
+
+        pool.execute(move || {
+            started.send(()).unwrap();
+            on_release.recv().unwrap();
+        });
+
+        on_start.recv().unwrap();
+        assert!(backlog.is_empty());
+
+        pool.execute(|| {});
+        assert!(!backlog.is_empty());
+
+        release.send(()).unwrap();
+        drop(pool);
+        assert!(backlog.is_empty());
+    }
+}
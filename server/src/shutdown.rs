@@ -0,0 +1,149 @@
+use std::{sync::{Arc, Mutex, Condvar, atomic::{AtomicBool, Ordering}}, time::{Duration, Instant}};
+
+/// Handle used to stop a running `Server`, it can be cloned and sent to other threads.
+///
+/// After `shutdown` is called the server stops accepting connections, waits for the requests
+/// being handled to finish (up to the configured shutdown timeout) and `run`/`serve` return.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownHandle {
+    state: Arc<ShutdownState>,
+}
+
+#[derive(Debug, Default)]
+struct ShutdownState {
+    // Kept in its own Arc so it can be registered as a signal flag
+    requested: Arc<AtomicBool>,
+    in_flight: Mutex<usize>,
+    drained: Condvar,
+}
+
+impl ShutdownHandle {
+    pub(in crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shutdown(&self) {
+        self.state.requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.state.requested.load(Ordering::SeqCst)
+    }
+
+    /// Makes SIGTERM and SIGINT (Ctrl+C) trigger the shutdown.
+    pub(in crate) fn register_signals(&self) {
+        for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+            if let Err(err) = signal_hook::flag::register(signal, Arc::clone(&self.state.requested)) {
+                println!("Failed to register handler for signal {signal}: {err}");
+            }
+        }
+    }
+
+    /// Marks a request as in flight, returns `None` when the server is already shutting down
+    /// and the request shouldn't be handled.
+    pub(in crate) fn start_request(&self) -> Option<RequestGuard> {
+        *self.state.in_flight.lock().unwrap() += 1;
+
+        let guard = RequestGuard { handle: self.clone() };
+
+        match self.is_shutdown() {
+            true => None,
+            false => Some(guard),
+        }
+    }
+
+    /// Blocks until every in flight request finished or `timeout` passed, returns whether
+    /// every request finished.
+    pub(in crate) fn wait_drained(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut in_flight = self.state.in_flight.lock().unwrap();
+
+        while *in_flight > 0 {
+            let now = Instant::now();
+
+            if now >= deadline {
+                return false;
+            }
+
+            in_flight = self.state.drained.wait_timeout(in_flight, deadline - now).unwrap().0;
+        }
+
+        true
+    }
+
+    #[cfg(feature = "async")]
+    pub(in crate) async fn requested(&self) {
+        while !self.is_shutdown() {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(in crate) async fn wait_drained_async(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        while *self.state.in_flight.lock().unwrap() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+
+        true
+    }
+}
+
+/// How often the accept loop checks whether a shutdown was requested.
+pub(in crate) const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Keeps a request counted as in flight until it's dropped.
+pub(in crate) struct RequestGuard {
+    handle: ShutdownHandle,
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        let mut in_flight = self.handle.state.in_flight.lock().unwrap();
+        *in_flight -= 1;
+
+        if *in_flight == 0 {
+            self.handle.state.drained.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn refuses_requests_after_shutdown() {
+        let handle = ShutdownHandle::new();
+
+        assert!(handle.start_request().is_some());
+
+        handle.shutdown();
+
+        assert!(handle.is_shutdown());
+        assert!(handle.start_request().is_none());
+        assert!(handle.wait_drained(Duration::ZERO));
+    }
+
+    #[test]
+    fn waits_for_in_flight_requests() {
+        let handle = ShutdownHandle::new();
+        let guard = handle.start_request().unwrap();
+
+        assert!(!handle.wait_drained(Duration::from_millis(20)));
+
+        let worker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(guard);
+        });
+
+        assert!(handle.wait_drained(Duration::from_secs(5)));
+        worker.join().unwrap();
+    }
+}
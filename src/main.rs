@@ -4,21 +4,49 @@
 use std::collections::HashMap;
 
 use serde_derive::{Deserialize, Serialize};
-use server::{server::Server, model::{enums::status_code::StatusCode, response_entity::{ResponseEntityBuilder, ResponseEntity}}};
+use server::{server::Server, extract::{RequestContext, Path}, middleware::Next, model::{enums::status_code::StatusCode, header_map::HeaderMap, response_entity::{ResponseEntityBuilder, ResponseEntity}, api_error::ApiError}};
 
 fn main() {
-    let mut server = Server::new("127.0.0.1:8080".to_string());
-    server.mount(get!["/hello", hello]);
+    let mut server = Server::new("127.0.0.1:8080".to_string())
+        .with_signal_handling()
+        .with_middleware(log_request);
+    server.mount(get_route!["/hello", hello]);
+    server.mount(get_route!["/health", health, no_cache]);
+    server.nest("/", routes![greet]);
     server.run();
 }
 
-fn hello(_headers: HashMap<String, String>, _params: HashMap<String, String>, req: HelloRequest) -> ResponseEntity {
+fn hello(_headers: HeaderMap, _params: HashMap<String, String>, req: HelloRequest) -> Result<ResponseEntity, ApiError> {
     ResponseEntityBuilder::new()
         .with_body(HelloResponse { message: format!("Hello, {}", req.name) })
         .with_status_code(StatusCode::Ok)
         .build()
 }
 
+fn health() -> StatusCode {
+    StatusCode::Ok
+}
+
+#[get("/greet/{name}")]
+fn greet(Path(name): Path<String>) -> Result<ResponseEntity, ApiError> {
+    ResponseEntityBuilder::new()
+        .with_body(HelloResponse { message: format!("Hello, {name}") })
+        .with_status_code(StatusCode::Ok)
+        .build()
+}
+
+fn log_request(context: RequestContext, next: Next) -> ResponseEntity {
+    let line = format!("{} {}", context.request().method, context.request().path);
+    let response = next.run(context);
+
+    println!("{line} -> {}", response.status().status_number());
+    response
+}
+
+fn no_cache(context: RequestContext, next: Next) -> ResponseEntity {
+    next.run(context).with_header("Cache-Control", "no-store")
+}
+
 #[derive(Request)]
 #[request_obj]
 struct HelloRequest {